@@ -0,0 +1,34 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use hd2re::hash::{stingray_hash, stingray_hash_batch};
+
+/// Candidates shaped like the cracker ones: a directory, a word and a suffix
+fn candidates(len: usize) -> Vec<String> {
+    (0..len)
+        .map(|i| format!("content/fac_helldivers/weapons/word_{i}_lod{}", i % 4))
+        .collect()
+}
+
+fn hash(c: &mut Criterion) {
+    let mut group = c.benchmark_group("stingray_hash");
+    for len in [64, 1024] {
+        let names = candidates(len);
+        let keys: Vec<&[u8]> = names.iter().map(String::as_bytes).collect();
+        let mut out = vec![0; keys.len()];
+        group.throughput(Throughput::Elements(len as u64));
+        group.bench_with_input(BenchmarkId::new("scalar", len), &keys, |b, keys| {
+            b.iter(|| {
+                for (key, out) in keys.iter().zip(out.iter_mut()) {
+                    *out = stingray_hash(key);
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("batch", len), &keys, |b, keys| {
+            b.iter(|| stingray_hash_batch(keys, &mut out))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, hash);
+criterion_main!(benches);
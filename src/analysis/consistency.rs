@@ -0,0 +1,244 @@
+//! Whether the sniffers agree with the type of the assets, part by part.
+//!
+//! Within a known type, a part is expected to sniff the same way for almost every asset: the few
+//! that don't are outliers worth a look. For the unknown types, the dominant label hints at what
+//! they are. Every sniffer has its own labels, signature names, magika labels, libmagic
+//! descriptions, MIME types or extensions, so they are counted per sniffer, normalized by it.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use crate::decode::serialize_hex;
+use crate::index::{HD2Index, Part};
+use crate::parse::DataType;
+use crate::sniff::Registry;
+
+/// Label of a non empty part the sniffer didn't recognize
+const NO_GUESS: &str = "none";
+/// Share of a type's parts a label needs to be the expected one
+const DOMINANT: f32 = 0.8;
+/// Below this many parts, a type doesn't say what to expect
+const MIN_PARTS: usize = 5;
+
+#[derive(Debug, Serialize)]
+pub struct LabelCount {
+    pub label: String,
+    pub count: usize,
+}
+
+/// Labels a sniffer gave to one part of every asset of a type, most frequent first
+#[derive(Debug, Serialize)]
+pub struct TypeLabels {
+    pub sniffer: String,
+    pub type_id: DataType,
+    pub part: Part,
+    pub total: usize,
+    pub labels: Vec<LabelCount>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Outlier {
+    #[serde(serialize_with = "serialize_hex")]
+    pub id: u64,
+    pub sniffer: String,
+    pub type_id: DataType,
+    pub part: Part,
+    pub label: String,
+    pub confidence: f32,
+    /// Label of most of the type
+    pub expected: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Recommendation {
+    pub sniffer: String,
+    pub type_id: DataType,
+    pub part: Part,
+    pub label: String,
+    /// Share of the type's parts with the label
+    pub share: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConsistencyReport {
+    pub table: Vec<TypeLabels>,
+    pub outliers: Vec<Outlier>,
+    pub recommendations: Vec<Recommendation>,
+}
+
+/// Asset, label and confidence of the best guess for a part
+type Sniffed = (u64, String, f32);
+
+fn part_size(index: &HD2Index, id: u64, part: Part) -> u32 {
+    let r = &index[id].record;
+    match part {
+        Part::Data => r.data_size,
+        Part::Stream => r.stream_size,
+        Part::Gpu => r.gpu_size,
+    }
+}
+
+impl TypeLabels {
+    /// The label of most parts, if there is one
+    fn dominant(&self) -> Option<&LabelCount> {
+        self.labels
+            .first()
+            .filter(|_| self.total >= MIN_PARTS)
+            .filter(|l| l.count as f32 >= self.total as f32 * DOMINANT)
+    }
+}
+
+/// Compare the best guess of each of `sniffers` for every part with the type of its asset. The
+/// sniffers must have been [`Registry::run`].
+pub fn consistency(index: &HD2Index, sniffers: &Registry) -> ConsistencyReport {
+    // Every non empty part, by sniffer, type and part
+    let mut sniffed: HashMap<(&str, DataType, Part), Vec<Sniffed>> = HashMap::new();
+    for id in index.ids() {
+        let type_id = index[id].record.type_id;
+        for part in Part::iter() {
+            if part_size(index, id, part) == 0 {
+                continue;
+            }
+            for (sniffer, guess) in sniffers.best_guesses(id, part) {
+                let (label, confidence) =
+                    guess.map_or((NO_GUESS.to_owned(), 0.0), |g| (g.label, g.confidence));
+                sniffed
+                    .entry((sniffer, type_id, part))
+                    .or_default()
+                    .push((id, label, confidence));
+            }
+        }
+    }
+
+    let mut table = Vec::new();
+    let mut outliers = Vec::new();
+    let mut recommendations = Vec::new();
+    for ((sniffer, type_id, part), parts) in sniffed {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for (_, label, _) in &parts {
+            *counts.entry(label).or_default() += 1;
+        }
+        let mut labels: Vec<_> = counts
+            .into_iter()
+            .map(|(label, count)| LabelCount {
+                label: label.to_owned(),
+                count,
+            })
+            .collect();
+        labels.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.label.cmp(&b.label)));
+        let labels = TypeLabels {
+            sniffer: sniffer.to_owned(),
+            type_id,
+            part,
+            total: parts.len(),
+            labels,
+        };
+
+        if let Some(dominant) = labels.dominant() {
+            if type_id.is_known() {
+                outliers.extend(
+                    parts
+                        .into_iter()
+                        .filter(|(_, label, _)| *label != dominant.label)
+                        .map(|(id, label, confidence)| Outlier {
+                            id,
+                            sniffer: sniffer.to_owned(),
+                            type_id,
+                            part,
+                            label,
+                            confidence,
+                            expected: dominant.label.clone(),
+                        }),
+                );
+            }
+        }
+        if !type_id.is_known() {
+            if let Some(top) = labels.labels.first().filter(|l| l.label != NO_GUESS) {
+                recommendations.push(Recommendation {
+                    sniffer: sniffer.to_owned(),
+                    type_id,
+                    part,
+                    label: top.label.clone(),
+                    share: top.count as f32 / labels.total as f32,
+                });
+            }
+        }
+        table.push(labels);
+    }
+
+    table.sort_by_key(|t| (t.type_id.to_string(), t.part as u8, t.sniffer.clone()));
+    // Confident disagreements first
+    outliers.sort_by(|a, b| {
+        b.confidence
+            .total_cmp(&a.confidence)
+            .then(a.id.cmp(&b.id))
+            .then((a.part as u8).cmp(&(b.part as u8)))
+            .then(a.sniffer.cmp(&b.sniffer))
+    });
+    recommendations.sort_by_key(|r| (r.type_id.to_string(), r.part as u8, r.sniffer.clone()));
+    ConsistencyReport {
+        table,
+        outliers,
+        recommendations,
+    }
+}
+
+/// Print the most frequent labels of every type and part, the outliers and the recommendations
+pub fn print_report(report: &ConsistencyReport) {
+    println!(
+        "{:<32} {:<6} {:<14} {:>8}  labels",
+        "type", "part", "sniffer", "parts"
+    );
+    for t in &report.table {
+        let labels: Vec<_> = t
+            .labels
+            .iter()
+            .take(3)
+            .map(|l| {
+                format!(
+                    "{} {:.0}%",
+                    l.label,
+                    l.count as f32 * 100.0 / t.total as f32
+                )
+            })
+            .collect();
+        println!(
+            "{:<32} {:<6} {:<14} {:>8}  {}",
+            t.type_id.to_string(),
+            t.part.to_string(),
+            t.sniffer,
+            t.total,
+            labels.join(", ")
+        );
+    }
+
+    println!();
+    println!("Outliers: {}", report.outliers.len());
+    for o in report.outliers.iter().take(50) {
+        println!(
+            "  {:016x} {:<24} {:<6} {:<14} {} ({:.2}), expected {}",
+            o.id,
+            o.type_id.to_string(),
+            o.part.to_string(),
+            o.sniffer,
+            o.label,
+            o.confidence,
+            o.expected
+        );
+    }
+
+    println!();
+    println!("Recommended labels:");
+    for r in &report.recommendations {
+        println!(
+            "  {:<24} {:<6} {:<14} {} ({:.0}%)",
+            r.type_id.to_string(),
+            r.part.to_string(),
+            r.sniffer,
+            r.label,
+            r.share * 100.0
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! How much of the index the dictionary names, to know where to focus cracking.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::decode::serialize_hex;
+use crate::hash::Dictionary;
+use crate::index::HD2Index;
+use crate::parse::DataType;
+
+/// How many of the biggest unnamed assets are listed
+const BIGGEST: usize = 50;
+
+#[derive(Debug, Serialize)]
+pub struct TypeCoverage {
+    pub type_id: DataType,
+    pub total: usize,
+    pub named: usize,
+    pub suspicious: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileCoverage {
+    #[serde(serialize_with = "serialize_hex")]
+    pub file_id: u64,
+    pub total: usize,
+    pub named: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Asset {
+    #[serde(serialize_with = "serialize_hex")]
+    pub id: u64,
+    pub type_id: DataType,
+    pub name: Option<String>,
+    /// Data, stream and gpu sizes summed
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CoverageReport {
+    pub total: usize,
+    pub named: usize,
+    pub by_type: Vec<TypeCoverage>,
+    pub by_file: Vec<FileCoverage>,
+    /// Names that probably aren't the real one
+    pub suspicious: Vec<Asset>,
+    pub biggest_unnamed: Vec<Asset>,
+}
+
+/// The hash comes from a file name so it most probably contains a '/'
+pub fn is_suspicious(name: &str) -> bool {
+    !name.contains('/')
+}
+
+pub fn coverage(index: &HD2Index, dictionary: &Dictionary) -> CoverageReport {
+    let mut by_type: HashMap<DataType, TypeCoverage> = HashMap::new();
+    let mut by_file: HashMap<u64, FileCoverage> = HashMap::new();
+    let mut suspicious = Vec::new();
+    let mut unnamed = Vec::new();
+    let mut named = 0;
+    for id in index.ids() {
+        let entry = &index[id];
+        let r = &entry.record;
+        let name = dictionary.get(id);
+        let ty = by_type.entry(r.type_id).or_insert(TypeCoverage {
+            type_id: r.type_id,
+            total: 0,
+            named: 0,
+            suspicious: 0,
+        });
+        let file = by_file.entry(entry.file_id).or_insert(FileCoverage {
+            file_id: entry.file_id,
+            total: 0,
+            named: 0,
+        });
+        ty.total += 1;
+        file.total += 1;
+        let asset = Asset {
+            id,
+            type_id: r.type_id,
+            name: name.map(str::to_owned),
+            size: r.data_size as u64 + r.stream_size as u64 + r.gpu_size as u64,
+        };
+        match name {
+            Some(name) => {
+                named += 1;
+                ty.named += 1;
+                file.named += 1;
+                if is_suspicious(name) {
+                    ty.suspicious += 1;
+                    suspicious.push(asset);
+                }
+            }
+            None => unnamed.push(asset),
+        }
+    }
+
+    let mut by_type: Vec<_> = by_type.into_values().collect();
+    // Most unnamed first
+    by_type.sort_by_key(|t| (Reverse(t.total - t.named), t.type_id.to_string()));
+    let mut by_file: Vec<_> = by_file.into_values().collect();
+    by_file.sort_by_key(|f| f.file_id);
+    suspicious.sort_by_key(|a| a.id);
+    unnamed.sort_by_key(|a| (Reverse(a.size), a.id));
+    unnamed.truncate(BIGGEST);
+    CoverageReport {
+        total: index.len(),
+        named,
+        by_type,
+        by_file,
+        suspicious,
+        biggest_unnamed: unnamed,
+    }
+}
+
+fn percent(named: usize, total: usize) -> f32 {
+    named as f32 * 100.0 / total.max(1) as f32
+}
+
+/// Print the coverage per type, the least covered archives and the biggest unnamed assets
+pub fn print_report(report: &CoverageReport) {
+    println!(
+        "Named {}/{} assets ({:.1}%), {} suspicious",
+        report.named,
+        report.total,
+        percent(report.named, report.total),
+        report.suspicious.len()
+    );
+    println!();
+    println!(
+        "{:<32} {:>8} {:>8} {:>6} {:>10}",
+        "type", "assets", "named", "%", "suspicious"
+    );
+    for t in &report.by_type {
+        println!(
+            "{:<32} {:>8} {:>8} {:>6.1} {:>10}",
+            t.type_id.to_string(),
+            t.total,
+            t.named,
+            percent(t.named, t.total),
+            t.suspicious
+        );
+    }
+
+    println!();
+    println!("Least covered archives:");
+    let mut files: Vec<_> = report.by_file.iter().collect();
+    files.sort_by(|a, b| {
+        percent(a.named, a.total)
+            .total_cmp(&percent(b.named, b.total))
+            .then(b.total.cmp(&a.total))
+    });
+    for f in files.iter().take(20) {
+        println!(
+            "  {:016x} {:>6}/{:<6} {:>6.1}%",
+            f.file_id,
+            f.named,
+            f.total,
+            percent(f.named, f.total)
+        );
+    }
+
+    println!();
+    println!("Biggest unnamed assets:");
+    for a in report.biggest_unnamed.iter().take(20) {
+        println!(
+            "  {:016x} {:<32} {:>12}",
+            a.id,
+            a.type_id.to_string(),
+            a.size
+        );
+    }
+
+    println!();
+    println!("Suspicious names:");
+    for a in report.suspicious.iter().take(20) {
+        println!(
+            "  {:016x} {:<32} {}",
+            a.id,
+            a.type_id.to_string(),
+            a.name.as_deref().unwrap_or("")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::coverage::coverage;
+    use crate::hash::{stingray_hash, Dictionary};
+    use crate::index::HD2Index;
+    use crate::parse::DataType;
+
+    #[test]
+    fn named_per_type_and_archive() {
+        let named = stingray_hash(b"content/units/a");
+        let suspicious = stingray_hash(b"bone_root");
+        let index = HD2Index::from_data_parts(
+            "",
+            &[
+                (1, named, DataType::unit, 0, 10),
+                (1, suspicious, DataType::unit, 10, 10),
+                (2, 0x1234, DataType::lua, 0, 100),
+                (2, 0x5678, DataType::lua, 100, 300),
+            ],
+        );
+        let mut dictionary = Dictionary::default();
+        dictionary.add_names("test", 0, ["content/units/a", "bone_root", "unused"]);
+
+        let report = coverage(&index, &dictionary);
+        assert_eq!((report.total, report.named), (4, 2));
+        let by_type: Vec<_> = report
+            .by_type
+            .iter()
+            .map(|t| (t.type_id, t.total, t.named, t.suspicious))
+            .collect();
+        assert_eq!(
+            by_type,
+            [(DataType::lua, 2, 0, 0), (DataType::unit, 2, 2, 1)]
+        );
+        let by_file: Vec<_> = report
+            .by_file
+            .iter()
+            .map(|f| (f.file_id, f.total, f.named))
+            .collect();
+        assert_eq!(by_file, [(1, 2, 2), (2, 2, 0)]);
+        let ids = |assets: &[crate::analysis::coverage::Asset]| -> Vec<u64> {
+            assets.iter().map(|a| a.id).collect()
+        };
+        assert_eq!(ids(&report.suspicious), [suspicious]);
+        assert_eq!(ids(&report.biggest_unnamed), [0x5678, 0x1234]);
+    }
+}
@@ -0,0 +1,6 @@
+//! Analyses across many assets, to help reversing what isn't decoded yet.
+
+pub mod consistency;
+pub mod coverage;
+pub mod workbench;
+pub mod xref;
@@ -0,0 +1,347 @@
+//! Statistics over all the instances of a type, to help reversing its layout.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use crate::decode::scan::is_plausible_float;
+use crate::decode::{f32_at, u32_at, u64_at, write_json};
+use crate::index::{HD2Index, Part};
+use crate::parse::DataType;
+
+/// Only the start of the data part is analyzed field by field
+const MAX_OFFSET: usize = 256;
+/// Ratio of instances that must agree on a field guess
+const THRESHOLD: f32 = 0.9;
+
+#[derive(Debug, Serialize)]
+pub struct SizeStats {
+    pub part: Part,
+    /// Instances with this part
+    pub count: usize,
+    pub min: u32,
+    pub max: u32,
+    pub mean: f64,
+    pub median: u32,
+    /// Instances per power of two bucket, as (upper bound, count)
+    pub histogram: Vec<(u64, usize)>,
+    /// Most common first 4 bytes, as (value, count)
+    pub magics: Vec<(String, usize)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldGuess {
+    pub offset: usize,
+    /// Instances long enough to have this field
+    pub samples: usize,
+    /// Shannon entropy of each of the 4 bytes, in bits
+    pub entropy: [f32; 4],
+    pub kind: FieldKind,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FieldKind {
+    Constant {
+        value: String,
+    },
+    /// Equals the data part size
+    Size,
+    AssetId {
+        types: Vec<(DataType, usize)>,
+    },
+    F32 {
+        min: f32,
+        max: f32,
+    },
+    U32 {
+        min: u32,
+        max: u32,
+    },
+    Unknown,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkbenchReport {
+    pub type_id: DataType,
+    pub type_hash: String,
+    pub instances: usize,
+    pub sizes: Vec<SizeStats>,
+    /// Bytes shared by all the data parts
+    pub common_prefix: String,
+    /// Shannon entropy of the byte at each offset, in bits
+    pub byte_entropy: Vec<f32>,
+    pub fields: Vec<FieldGuess>,
+}
+
+fn entropy(values: impl Iterator<Item = u8>) -> f32 {
+    let mut counts = [0usize; 256];
+    let mut total = 0;
+    for v in values {
+        counts[v as usize] += 1;
+        total += 1;
+    }
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f32 / total as f32;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn size_stats(index: &HD2Index, ids: &[u64], part: Part) -> Option<SizeStats> {
+    let mut sizes: Vec<(u64, u32)> = ids
+        .iter()
+        .map(|&id| {
+            let r = &index[id].record;
+            let size = match part {
+                Part::Data => r.data_size,
+                Part::Stream => r.stream_size,
+                Part::Gpu => r.gpu_size,
+            };
+            (id, size)
+        })
+        .filter(|&(_, size)| size > 0)
+        .collect();
+    if sizes.is_empty() {
+        return None;
+    }
+    sizes.sort_by_key(|s| s.1);
+
+    let mut histogram: Vec<(u64, usize)> = Vec::new();
+    for &(_, size) in &sizes {
+        let bucket = (size as u64).next_power_of_two();
+        match histogram.last_mut() {
+            Some((b, count)) if *b == bucket => *count += 1,
+            _ => histogram.push((bucket, 1)),
+        }
+    }
+
+    let mut magics = HashMap::new();
+    for &(id, _) in &sizes {
+        let magic = match part {
+            Part::Data => index.load_n_data_bytes::<4>(id),
+            Part::Stream => index.load_n_stream_bytes::<4>(id),
+            Part::Gpu => index.load_n_gpu_bytes::<4>(id),
+        };
+        if let Ok(magic) = magic {
+            *magics.entry(u32::from_le_bytes(magic)).or_insert(0) += 1;
+        }
+    }
+    let mut magics: Vec<_> = magics.into_iter().collect();
+    magics.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    magics.truncate(8);
+
+    Some(SizeStats {
+        part,
+        count: sizes.len(),
+        min: sizes[0].1,
+        max: sizes[sizes.len() - 1].1,
+        mean: sizes.iter().map(|s| s.1 as f64).sum::<f64>() / sizes.len() as f64,
+        median: sizes[sizes.len() / 2].1,
+        histogram,
+        magics: magics
+            .into_iter()
+            .map(|(m, c)| (format!("{m:08x}"), c))
+            .collect(),
+    })
+}
+
+fn guess_field(index: &HD2Index, samples: &[(u32, &[u8])], offset: usize) -> FieldKind {
+    let u32s: Vec<u32> = samples
+        .iter()
+        .filter_map(|(_, b)| u32_at(b, offset))
+        .collect();
+    if u32s.is_empty() {
+        return FieldKind::Unknown;
+    }
+    let n = u32s.len() as f32;
+    if samples.len() > 1 && u32s.iter().all(|&v| v == u32s[0]) {
+        return FieldKind::Constant {
+            value: format!("{:08x}", u32s[0]),
+        };
+    }
+    let sizes = samples
+        .iter()
+        .filter(|(size, b)| u32_at(b, offset) == Some(*size))
+        .count();
+    if sizes as f32 >= n * THRESHOLD {
+        return FieldKind::Size;
+    }
+
+    let mut types = HashMap::new();
+    for (_, b) in samples {
+        if let Some(entry) = u64_at(b, offset).and_then(|v| index.get(v)) {
+            *types.entry(entry.record.type_id).or_insert(0) += 1;
+        }
+    }
+    if types.values().sum::<usize>() as f32 >= n * 0.5 {
+        let mut types: Vec<_> = types.into_iter().collect();
+        types.sort_by_key(|t| Reverse(t.1));
+        return FieldKind::AssetId { types };
+    }
+
+    let floats: Vec<f32> = samples
+        .iter()
+        .filter_map(|(_, b)| f32_at(b, offset))
+        .collect();
+    let plausible = floats.iter().filter(|&&f| is_plausible_float(f)).count();
+    if plausible as f32 >= n * THRESHOLD && floats.iter().any(|&f| f != 0.0) {
+        let min = floats.iter().copied().fold(f32::MAX, f32::min);
+        let max = floats.iter().copied().fold(f32::MIN, f32::max);
+        return FieldKind::F32 { min, max };
+    }
+    let small = u32s.iter().filter(|&&v| v < 0x10000).count();
+    if small as f32 >= n * THRESHOLD {
+        let min = *u32s.iter().min().unwrap();
+        let max = *u32s.iter().max().unwrap();
+        return FieldKind::U32 { min, max };
+    }
+    FieldKind::Unknown
+}
+
+pub fn analyze(index: &HD2Index, ty: DataType) -> WorkbenchReport {
+    let mut ids: Vec<_> = index.ids_of_type(ty).collect();
+    ids.sort();
+    let sizes = Part::iter()
+        .filter_map(|part| size_stats(index, &ids, part))
+        .collect();
+
+    let data: Vec<(u32, Vec<u8>)> = ids
+        .iter()
+        .filter_map(|&id| {
+            let mut bytes = index.load_data_bytes(id).ok()?;
+            bytes.truncate(MAX_OFFSET);
+            Some((index[id].record.data_size, bytes))
+        })
+        .collect();
+    let samples: Vec<(u32, &[u8])> = data.iter().map(|(s, b)| (*s, b.as_slice())).collect();
+
+    let common_prefix = match samples.first() {
+        Some((_, first)) => {
+            let len = (0..first.len())
+                .take_while(|&i| samples.iter().all(|(_, b)| b.get(i) == Some(&first[i])))
+                .count();
+            first[..len].iter().map(|b| format!("{b:02x}")).collect()
+        }
+        None => String::new(),
+    };
+
+    let longest = samples.iter().map(|(_, b)| b.len()).max().unwrap_or(0);
+    let byte_entropy: Vec<_> = (0..longest)
+        .map(|offset| entropy(samples.iter().filter_map(|(_, b)| b.get(offset).copied())))
+        .collect();
+    let fields = (0..longest)
+        .step_by(4)
+        .map(|offset| FieldGuess {
+            offset,
+            samples: samples
+                .iter()
+                .filter(|(_, b)| b.len() >= offset + 4)
+                .count(),
+            entropy: std::array::from_fn(|i| byte_entropy.get(offset + i).copied().unwrap_or(0.0)),
+            kind: guess_field(index, &samples, offset),
+        })
+        .collect();
+
+    WorkbenchReport {
+        type_id: ty,
+        type_hash: format!("{:016x}", ty as u64),
+        instances: ids.len(),
+        sizes,
+        common_prefix,
+        byte_entropy,
+        fields,
+    }
+}
+
+/// An ImHex pattern of the guessed fields, to keep going in the hex editor
+pub fn to_hexpat(report: &WorkbenchReport) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "// {} ({}), generated by hd2re",
+        report.type_id, report.type_hash
+    )
+    .unwrap();
+    writeln!(out, "// {} instances", report.instances).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "struct {} {{", report.type_id).unwrap();
+    let mut i = 0;
+    while i < report.fields.len() {
+        let field = &report.fields[i];
+        let offset = field.offset;
+        let line = match &field.kind {
+            FieldKind::Constant { value } => format!("u32 const_{offset:02x}; // always 0x{value}"),
+            FieldKind::Size => format!("u32 size_{offset:02x}; // data size"),
+            FieldKind::AssetId { types } => {
+                // Ids take the next slot too
+                i += 1;
+                let types: Vec<_> = types.iter().map(|(t, c)| format!("{t} x{c}")).collect();
+                format!("u64 asset_{offset:02x}; // {}", types.join(", "))
+            }
+            FieldKind::F32 { min, max } => format!("float f32_{offset:02x}; // {min} .. {max}"),
+            FieldKind::U32 { min, max } => format!("u32 u32_{offset:02x}; // {min} .. {max}"),
+            FieldKind::Unknown => {
+                let [a, b, c, d] = field.entropy;
+                format!("u8 unk_{offset:02x}[4]; // entropy {a:.2} {b:.2} {c:.2} {d:.2}")
+            }
+        };
+        writeln!(out, "    {line}").unwrap();
+        i += 1;
+    }
+    writeln!(out, "}};").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "{} item @ 0x00;", report.type_id).unwrap();
+    out
+}
+
+/// Print a summary of the report
+pub fn print_summary(report: &WorkbenchReport) {
+    println!(
+        "{} ({}): {} instances",
+        report.type_id, report.type_hash, report.instances
+    );
+    for s in &report.sizes {
+        println!(
+            "  {:<6} {} instances, size {} .. {} (median {}, mean {:.0})",
+            s.part.to_string(),
+            s.count,
+            s.min,
+            s.max,
+            s.median,
+            s.mean
+        );
+        for (magic, count) in s.magics.iter().take(3) {
+            println!("         starts with {magic} x{count}");
+        }
+    }
+    println!("  common prefix: {}", report.common_prefix);
+    for f in &report.fields {
+        if f.kind != FieldKind::Unknown {
+            println!("  +{:#04x} {:?}", f.offset, f.kind);
+        }
+    }
+}
+
+/// Analyze `ty` and write `<type>.json` and `<type>.hexpat` to `out_dir`
+pub fn export(index: &HD2Index, ty: DataType, out_dir: impl AsRef<Path>) {
+    let out_dir = out_dir.as_ref();
+    let report = analyze(index, ty);
+    print_summary(&report);
+    write_json(out_dir.join(format!("{ty}.json")), &report);
+    fs::write(out_dir.join(format!("{ty}.hexpat")), to_hexpat(&report)).unwrap();
+}
+
+/// Parse a type from its name or its hash
+pub fn parse_type(s: &str) -> Option<DataType> {
+    let hash = u64::from_str_radix(s.trim_start_matches("0x"), 16).ok();
+    DataType::iter().find(|&t| t.to_string() == s || Some(t as u64) == hash)
+}
@@ -0,0 +1,113 @@
+//! Reverse reference index: which assets embed the id of a given asset or name.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use speedy::{Readable, Writable};
+
+use crate::decode::u64_at;
+use crate::hash::{stingray_hash, Dictionary, NoHash};
+use crate::index::{AssetMap, HD2Index};
+
+/// An asset holding a reference, and where in its data part
+#[derive(Debug, Readable, Writable, Copy, Clone)]
+pub struct Referrer {
+    pub id: u64,
+    pub offset: u32,
+}
+
+#[derive(Readable, Writable)]
+pub struct RefIndex {
+    /// [`Dictionary::fingerprint`] of the dictionary the index was built with
+    dictionary: u64,
+    /// Referenced id to the assets referencing it
+    referrers: AssetMap<Vec<Referrer>>,
+}
+
+impl RefIndex {
+    /// Scan the data part of every asset for 4 bytes aligned u64 that are either assets in `index`
+    /// or hashes in `dictionary`, like [`crate::decode::scan::find_refs`].
+    pub fn create_index(index: &HD2Index, dictionary: &Dictionary) -> Self {
+        let mut referrers: AssetMap<Vec<Referrer>> = HashMap::with_hasher(NoHash);
+        let start = Instant::now();
+        let mut ids: Vec<_> = index.ids().collect();
+        // Read the files in order
+        ids.sort_by_key(|&id| (index[id].file_id, index[id].record.offset));
+        for (i, &id) in ids.iter().enumerate() {
+            let Ok(data) = index.load_data_bytes(id) else {
+                continue;
+            };
+            for offset in (0..data.len().saturating_sub(7)).step_by(4) {
+                let value = u64_at(&data, offset).unwrap();
+                if value != 0
+                    && value != id
+                    && (index.contains(value) || dictionary.get(value).is_some())
+                {
+                    referrers.entry(value).or_default().push(Referrer {
+                        id,
+                        offset: offset as u32,
+                    });
+                }
+            }
+            if i % 10000 == 0 {
+                println!("Scanned {i}/{}", ids.len());
+            }
+        }
+        println!(
+            "Found references to {} ids in {} ms",
+            referrers.len(),
+            start.elapsed().as_millis()
+        );
+        Self {
+            dictionary: dictionary.fingerprint(),
+            referrers,
+        }
+    }
+
+    /// Whether the index was built with the hashes of `dictionary`, otherwise named references may
+    /// be missing or stale
+    pub fn matches(&self, dictionary: &Dictionary) -> bool {
+        self.dictionary == dictionary.fingerprint()
+    }
+
+    pub fn len(&self) -> usize {
+        self.referrers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.referrers.is_empty()
+    }
+
+    /// Assets referencing `id`, empty if none
+    pub fn referrers(&self, id: u64) -> &[Referrer] {
+        self.referrers.get(&id).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Parse an asset id from its 16 hex digits or its name
+pub fn parse_id(s: &str) -> u64 {
+    match u64::from_str_radix(s, 16) {
+        Ok(id) if s.len() == 16 => id,
+        _ => stingray_hash(s.as_bytes()),
+    }
+}
+
+/// Print one line per asset referencing `id`: its id, type, name and the offset of the reference
+pub fn print_referrers(refs: &RefIndex, index: &HD2Index, dictionary: &Dictionary, id: u64) {
+    let name = dictionary.get(id).unwrap_or("?");
+    match index.get(id) {
+        Some(e) => println!("{id:016x} {} {name}", e.record.type_id),
+        None => println!("{id:016x} (not an asset) {name}"),
+    }
+    let referrers = refs.referrers(id);
+    for r in referrers {
+        println!(
+            "  {:016x}  {:<24}  {:#08x}  {}",
+            r.id,
+            index[r.id].record.type_id.to_string(),
+            r.offset,
+            dictionary.get(r.id).unwrap_or("")
+        );
+    }
+    println!("{} references", referrers.len());
+}
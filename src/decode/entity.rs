@@ -0,0 +1,95 @@
+//! Named hashes and references of `entity` assets.
+//!
+//! Lists, in payload order, every 64-bit value that is a known name with the ways the 8 bytes after
+//! it can be read, and the assets the entity references. Components and their fields aren't
+//! decoded. Output is deterministic so that reports can be diffed between patches.
+
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::decode::scan::{find_refs, find_thin_names, is_plausible_float, AssetRef, ThinName};
+use crate::decode::{export_each, f32_at, serialize_hex, u32_at, u64_at, write_json};
+use crate::hash::Dictionary;
+use crate::index::HD2Index;
+use crate::parse::DataType;
+
+/// The candidate interpretations of the 8 bytes following a named hash
+#[derive(Debug, Serialize)]
+pub struct FollowingBytes {
+    pub u32: Option<u32>,
+    pub f32: Option<f32>,
+    /// Set if the value is an asset id in the index
+    pub asset: Option<DataType>,
+    #[serde(serialize_with = "serialize_hex")]
+    pub u64: u64,
+}
+
+/// A hash that isn't an asset but whose name is known
+#[derive(Debug, Serialize)]
+pub struct NamedHash {
+    pub offset: usize,
+    #[serde(serialize_with = "serialize_hex")]
+    pub hash: u64,
+    pub name: String,
+    pub next: Option<FollowingBytes>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EntityReport {
+    pub id: String,
+    pub name: Option<String>,
+    pub named_hashes: Vec<NamedHash>,
+    /// Known names by thin hash, string keys and enum values
+    pub thin_names: Vec<ThinName>,
+    /// References to other assets
+    pub references: Vec<AssetRef>,
+}
+
+fn read_following(index: &HD2Index, bytes: &[u8], offset: usize) -> Option<FollowingBytes> {
+    let u64 = u64_at(bytes, offset)?;
+    Some(FollowingBytes {
+        u32: u32_at(bytes, offset),
+        f32: f32_at(bytes, offset).filter(|&f| is_plausible_float(f)),
+        asset: index.get(u64).map(|e| e.record.type_id),
+        u64,
+    })
+}
+
+pub fn decode(index: &HD2Index, dictionary: &Dictionary, id: u64) -> io::Result<EntityReport> {
+    let data = index.load_data_bytes(id)?;
+    let mut named_hashes = Vec::new();
+    let mut references = Vec::new();
+    let refs = find_refs(&data, index, dictionary);
+    let thin_names = find_thin_names(&data, dictionary, &refs);
+    for r in refs {
+        match (r.type_id, &r.name) {
+            (None, Some(name)) => named_hashes.push(NamedHash {
+                offset: r.offset,
+                hash: r.id,
+                name: name.clone(),
+                next: read_following(index, &data, r.offset + 8),
+            }),
+            _ => references.push(r),
+        }
+    }
+    Ok(EntityReport {
+        id: format!("{id:016x}"),
+        name: dictionary.get(id).map(str::to_owned),
+        named_hashes,
+        thin_names,
+        references,
+    })
+}
+
+/// Export a `<asset id>.json` report for every entity to `out_dir`
+pub fn export_all(index: &HD2Index, dictionary: &Dictionary, out_dir: impl AsRef<Path>) {
+    let out_dir = out_dir.as_ref();
+    let count = export_each(index, &[DataType::entity], |id| {
+        let report = decode(index, dictionary, id)?;
+        write_json(out_dir.join(format!("{}.json", report.id)), &report);
+        Ok(())
+    });
+    println!("Exported {count} entities");
+}
@@ -0,0 +1,57 @@
+//! References of `flow` assets (visual scripting graphs).
+//!
+//! Node types, pins and variables are referred to by hashed names, so the known names are listed as
+//! symbols in payload order, 64-bit and thin hashes apart, next to the assets and the string
+//! literals the flow uses. The node and connection tables aren't decoded, so no graph is exported.
+
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::decode::scan::{
+    find_refs, find_strings, find_thin_names, AssetRef, FoundString, ThinName,
+};
+use crate::decode::{export_each, write_json};
+use crate::hash::Dictionary;
+use crate::index::HD2Index;
+use crate::parse::DataType;
+
+#[derive(Debug, Serialize)]
+pub struct FlowRefs {
+    pub id: String,
+    pub name: Option<String>,
+    /// Known names that aren't assets: node types, pins, variables, events
+    pub symbols: Vec<AssetRef>,
+    /// Known names by thin hash
+    pub thin_symbols: Vec<ThinName>,
+    /// Assets used as literal values
+    pub references: Vec<AssetRef>,
+    pub strings: Vec<FoundString>,
+}
+
+pub fn decode(index: &HD2Index, dictionary: &Dictionary, id: u64) -> io::Result<FlowRefs> {
+    let data = index.load_data_bytes(id)?;
+    let refs = find_refs(&data, index, dictionary);
+    let thin_symbols = find_thin_names(&data, dictionary, &refs);
+    let (references, symbols) = refs.into_iter().partition(|r| r.type_id.is_some());
+    Ok(FlowRefs {
+        id: format!("{id:016x}"),
+        name: dictionary.get(id).map(str::to_owned),
+        symbols,
+        thin_symbols,
+        references,
+        strings: find_strings(&data, 4),
+    })
+}
+
+/// Export a `<asset id>.json` reference report for every flow to `out_dir`
+pub fn export_all(index: &HD2Index, dictionary: &Dictionary, out_dir: impl AsRef<Path>) {
+    let out_dir = out_dir.as_ref();
+    let count = export_each(index, &[DataType::flow], |id| {
+        let report = decode(index, dictionary, id)?;
+        write_json(out_dir.join(format!("{}.json", report.id)), &report);
+        Ok(())
+    });
+    println!("Exported {count} flows");
+}
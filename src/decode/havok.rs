@@ -0,0 +1,415 @@
+//! `physics`, `havok_physics_properties` and `havok_ai_properties` assets.
+//!
+//! These embed Havok binary packfiles (up to hk2014) or chunked tagfiles (hk2015+). We locate
+//! the containers by their magic, walk the section and type tables and list the objects with their
+//! class. Object fields aren't reflected, so shapes aren't exported as geometry.
+
+use std::fmt::Write;
+use std::io::Cursor;
+use std::path::Path;
+
+use binrw::BinRead;
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use crate::decode::{cstr_at, export_each, u32_at, write_json};
+use crate::hash::Dictionary;
+use crate::index::{HD2Index, Part};
+use crate::parse::DataType;
+
+pub(crate) const PACKFILE_MAGIC: &[u8; 8] = b"\x57\xE0\xE0\x57\x10\xC0\xC0\x10";
+pub(crate) const TAGFILE_MAGIC: &[u8; 4] = b"TAG0";
+
+#[derive(BinRead, Debug)]
+#[br(little, magic = b"\x57\xE0\xE0\x57\x10\xC0\xC0\x10")]
+pub struct PackfileHeader {
+    pub user_tag: i32,
+    pub file_version: i32,
+    /// Pointer size, little endian, reuse padding, empty base class optimization
+    pub layout_rules: [u8; 4],
+    pub section_count: i32,
+    pub contents_section_index: i32,
+    pub contents_section_offset: i32,
+    pub contents_class_name_section_index: i32,
+    pub contents_class_name_section_offset: i32,
+    pub contents_version: [u8; 16],
+    pub flags: i32,
+    pub max_predicate: i16,
+    pub predicate_array_size_plus_padding: i16,
+}
+
+#[derive(BinRead, Debug)]
+#[br(little)]
+pub struct PackfileSection {
+    pub tag: [u8; 20],
+    pub absolute_data_start: u32,
+    /// Offsets relative to `absolute_data_start`
+    pub local_fixups_offset: u32,
+    pub global_fixups_offset: u32,
+    pub virtual_fixups_offset: u32,
+    pub exports_offset: u32,
+    pub imports_offset: u32,
+    pub end_offset: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Section {
+    pub name: String,
+    pub offset: usize,
+    pub size: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HavokObject {
+    pub class: Option<String>,
+    /// Offset from the start of the container
+    pub offset: usize,
+    pub size: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HavokContainer {
+    pub part: Part,
+    /// Offset of the container in the part
+    pub offset: usize,
+    pub format: &'static str,
+    pub version: String,
+    pub size: usize,
+    pub sections: Vec<Section>,
+    pub types: Vec<String>,
+    pub objects: Vec<HavokObject>,
+}
+
+impl HavokContainer {
+    fn parse_packfile(part: Part, offset: usize, bytes: &[u8]) -> Option<Self> {
+        let mut r = Cursor::new(bytes);
+        let header = PackfileHeader::read(&mut r).ok()?;
+        if !(0..=64).contains(&header.section_count) {
+            return None;
+        }
+        let mut section_offset = 64;
+        if header.file_version >= 11 && header.max_predicate != -1 {
+            section_offset += u64::try_from(header.predicate_array_size_plus_padding).ok()?;
+        }
+        let section_size = if header.file_version >= 11 { 64 } else { 48 };
+        let mut raw_sections = Vec::new();
+        for i in 0..header.section_count as u64 {
+            r.set_position(section_offset + i * section_size);
+            raw_sections.push(PackfileSection::read(&mut r).ok()?);
+        }
+
+        let size = raw_sections
+            .iter()
+            .map(|s| s.absolute_data_start as usize + s.end_offset as usize)
+            .max()
+            .unwrap_or(64);
+        let sections: Vec<_> = raw_sections
+            .iter()
+            .map(|s| Section {
+                name: cstr_at(&s.tag, 0).unwrap_or_default(),
+                offset: s.absolute_data_start as usize,
+                size: s.end_offset as usize,
+            })
+            .collect();
+        let classnames = sections.iter().position(|s| s.name == "__classnames__");
+
+        let mut types = Vec::new();
+        if let Some(classnames) = classnames.map(|i| &sections[i]) {
+            // u32 signature, 0x09, name\0, until padding
+            let mut pos = classnames.offset;
+            let end = (classnames.offset + classnames.size).min(bytes.len());
+            while pos + 5 < end && bytes[pos + 4] == 0x09 {
+                let name = cstr_at(bytes, pos + 5)?;
+                pos += 5 + name.len() + 1;
+                types.push(name);
+            }
+        }
+
+        let mut objects = Vec::new();
+        for s in &raw_sections {
+            let start = s.absolute_data_start as usize;
+            let mut fixup = start + s.virtual_fixups_offset as usize;
+            let mut section_objects = Vec::new();
+            while fixup + 12 <= start + s.exports_offset as usize {
+                let src = u32_at(bytes, fixup)?;
+                if src == u32::MAX {
+                    break;
+                }
+                let class_section = u32_at(bytes, fixup + 4)? as usize;
+                let class_offset = u32_at(bytes, fixup + 8)? as usize;
+                let class = raw_sections
+                    .get(class_section)
+                    .and_then(|cs| cstr_at(bytes, cs.absolute_data_start as usize + class_offset));
+                section_objects.push((start + src as usize, class));
+                fixup += 12;
+            }
+            objects.extend(with_sizes(
+                section_objects,
+                start + s.local_fixups_offset as usize,
+            ));
+        }
+
+        Some(Self {
+            part,
+            offset,
+            format: "packfile",
+            version: cstr_at(&header.contents_version, 0).unwrap_or_default(),
+            size,
+            sections,
+            types,
+            objects,
+        })
+    }
+
+    fn parse_tagfile(part: Part, offset: usize, bytes: &[u8]) -> Option<Self> {
+        // Anything after the root chunk isn't part of the container
+        let size = (u32::from_be_bytes(bytes.get(..4)?.try_into().ok()?) & 0x3FFFFFFF) as usize;
+        if size > bytes.len() {
+            return None;
+        }
+        let mut chunks = Vec::new();
+        read_chunks(bytes, 0, size, &mut chunks)?;
+        if chunks.first()?.0 != "TAG0" {
+            return None;
+        }
+        let find = |name: &str| chunks.iter().find(|c| c.0 == name).map(|c| (c.1, c.2));
+
+        let version = find("SDKV")
+            .map(|(o, s)| String::from_utf8_lossy(&bytes[o..o + s]).into_owned())
+            .unwrap_or_default();
+        let strings: Vec<String> = find("TSTR")
+            .map(|(o, s)| {
+                bytes[o..o + s]
+                    .split(|&b| b == 0)
+                    .map(|s| String::from_utf8_lossy(s).into_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let types = find("TNAM")
+            .and_then(|(o, s)| parse_type_names(&bytes[o..o + s], &strings))
+            .unwrap_or_default();
+
+        let mut objects = Vec::new();
+        if let (Some((items, items_size)), Some((data, data_size))) = (find("ITEM"), find("DATA")) {
+            let mut data_objects = Vec::new();
+            // First item is always null
+            for item in (items + 12..items + items_size).step_by(12) {
+                let ty = u32_at(bytes, item)? & 0xFFFFFF;
+                let item_offset = u32_at(bytes, item + 4)? as usize;
+                // Type 0 is the null type, names start at 1
+                let class = ty
+                    .checked_sub(1)
+                    .and_then(|t| types.get(t as usize))
+                    .cloned();
+                data_objects.push((data + item_offset, class));
+            }
+            objects = with_sizes(data_objects, data + data_size);
+        }
+
+        Some(Self {
+            part,
+            offset,
+            format: "tagfile",
+            version,
+            size,
+            sections: chunks
+                .into_iter()
+                .map(|(name, offset, size)| Section { name, offset, size })
+                .collect(),
+            types,
+            objects,
+        })
+    }
+
+    pub fn object_bytes<'a>(&self, container: &'a [u8], object: &HavokObject) -> &'a [u8] {
+        let start = object.offset.min(container.len());
+        &container[start..(object.offset + object.size).min(container.len())]
+    }
+}
+
+/// Objects are contiguous, their size is the distance to the next one
+fn with_sizes(mut objects: Vec<(usize, Option<String>)>, end: usize) -> Vec<HavokObject> {
+    objects.sort_by_key(|o| o.0);
+    let offsets: Vec<_> = objects.iter().map(|o| o.0).skip(1).chain([end]).collect();
+    objects
+        .into_iter()
+        .zip(offsets)
+        .map(|((offset, class), next)| HavokObject {
+            class,
+            offset,
+            size: next.saturating_sub(offset),
+        })
+        .collect()
+}
+
+/// Recursively collect the tagfile chunks as (name, payload offset, payload size)
+fn read_chunks(
+    bytes: &[u8],
+    mut pos: usize,
+    end: usize,
+    chunks: &mut Vec<(String, usize, usize)>,
+) -> Option<()> {
+    while pos + 8 <= end {
+        let header = u32::from_be_bytes(bytes.get(pos..pos + 4)?.try_into().ok()?);
+        let size = (header & 0x3FFFFFFF) as usize;
+        if size < 8 || pos + size > end {
+            return None;
+        }
+        let name = String::from_utf8_lossy(&bytes[pos + 4..pos + 8]).into_owned();
+        let container = matches!(name.as_str(), "TAG0" | "TYPE" | "INDX");
+        chunks.push((name, pos + 8, size - 8));
+        if container {
+            read_chunks(bytes, pos + 8, pos + size, chunks)?;
+        }
+        pos += size;
+    }
+    Some(())
+}
+
+/// Havok packed integers, the high bits of the first byte give the length
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Option<u32> {
+    let b = *bytes.get(*pos)? as u32;
+    let next = |n: usize| bytes.get(*pos + n).map(|&b| b as u32);
+    let (value, len) = match b >> 3 {
+        0x00..=0x0F => (b, 1),
+        0x10..=0x17 => (((b << 8) | next(1)?) & 0x3FFF, 2),
+        0x18..=0x1B => (((b << 16) | (next(1)? << 8) | next(2)?) & 0x1FFFFF, 3),
+        0x1C => (
+            ((b << 24) | (next(1)? << 16) | (next(2)? << 8) | next(3)?) & 0x7FFFFFF,
+            4,
+        ),
+        _ => return None,
+    };
+    *pos += len;
+    Some(value)
+}
+
+/// TNAM: type count, then for each type its name and template arguments as string indices.
+/// The count includes the null type at index 0 which isn't stored.
+fn parse_type_names(bytes: &[u8], strings: &[String]) -> Option<Vec<String>> {
+    let mut pos = 0;
+    let count = read_varint(bytes, &mut pos)?;
+    let mut types = Vec::with_capacity(count as usize);
+    for _ in 1..count {
+        let mut name = strings.get(read_varint(bytes, &mut pos)? as usize)?.clone();
+        let template_count = read_varint(bytes, &mut pos)?;
+        for _ in 0..template_count {
+            let arg = strings.get(read_varint(bytes, &mut pos)? as usize)?;
+            let value = read_varint(bytes, &mut pos)?;
+            write!(name, "<{arg}={value}>").unwrap();
+        }
+        types.push(name);
+    }
+    Some(types)
+}
+
+/// Find all the Havok containers in an asset payload
+pub fn find_containers(part: Part, bytes: &[u8]) -> Vec<HavokContainer> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while pos + 8 <= bytes.len() {
+        let window = &bytes[pos..];
+        let container = if window.starts_with(PACKFILE_MAGIC) {
+            HavokContainer::parse_packfile(part, pos, window)
+        } else if window.get(4..8) == Some(TAGFILE_MAGIC) {
+            HavokContainer::parse_tagfile(part, pos, window)
+        } else {
+            None
+        };
+        match container {
+            Some(c) => {
+                pos += c.size.max(8);
+                found.push(c);
+            }
+            None => pos += 4,
+        }
+    }
+    found
+}
+
+#[derive(Debug, Serialize)]
+pub struct HavokReport {
+    pub id: String,
+    pub name: Option<String>,
+    pub type_id: DataType,
+    pub containers: Vec<HavokContainer>,
+}
+
+pub fn decode(index: &HD2Index, dictionary: &Dictionary, id: u64) -> HavokReport {
+    let mut containers = Vec::new();
+    for part in Part::iter() {
+        if let Ok(bytes) = index.load_part_bytes(id, part) {
+            containers.extend(find_containers(part, &bytes));
+        }
+    }
+    HavokReport {
+        id: format!("{id:016x}"),
+        name: dictionary.get(id).map(str::to_owned),
+        type_id: index[id].record.type_id,
+        containers,
+    }
+}
+
+/// Export a `<asset id>.json` report for every Havok asset to `out_dir`
+pub fn export_all(index: &HD2Index, dictionary: &Dictionary, out_dir: impl AsRef<Path>) {
+    let out_dir = out_dir.as_ref();
+    let types = [
+        DataType::physics,
+        DataType::havok_physics_properties,
+        DataType::havok_ai_properties,
+    ];
+    let count = export_each(index, &types, |id| {
+        let report = decode(index, dictionary, id);
+        write_json(out_dir.join(format!("{}.json", report.id)), &report);
+        Ok(())
+    });
+    println!("Exported {count} Havok assets");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varints() {
+        let read = |bytes: &[u8]| {
+            let mut pos = 0;
+            read_varint(bytes, &mut pos).map(|v| (v, pos))
+        };
+        assert_eq!(read(&[0x05]), Some((5, 1)));
+        assert_eq!(read(&[0x81, 0x02]), Some((0x0102, 2)));
+        assert_eq!(read(&[0xC1, 0x02, 0x03]), Some((0x010203, 3)));
+        assert_eq!(read(&[0xE1, 0x02, 0x03, 0x04]), Some((0x01020304, 4)));
+        assert_eq!(read(&[0xF8]), None);
+        assert_eq!(read(&[0x81]), None);
+    }
+
+    #[test]
+    fn type_names() {
+        let strings = ["hkRootLevelContainer", "hkArray", "tT"].map(String::from);
+        // 2 types and the null one, the second with a template argument
+        let bytes = [0x03, 0x00, 0x00, 0x01, 0x01, 0x02, 0x05];
+        assert_eq!(
+            parse_type_names(&bytes, &strings).unwrap(),
+            ["hkRootLevelContainer", "hkArray<tT=5>"]
+        );
+        assert_eq!(parse_type_names(&[0x02, 0x07, 0x00], &strings), None);
+    }
+
+    #[test]
+    fn packfile_sections_past_u32() {
+        let mut bytes = PACKFILE_MAGIC.to_vec();
+        bytes.extend([0; 4]);
+        bytes.extend(8i32.to_le_bytes());
+        bytes.extend([8, 1, 0, 1]);
+        bytes.extend(1i32.to_le_bytes());
+        bytes.resize(64, 0);
+        bytes.extend(b"__data__".iter().chain(&[0; 12]));
+        bytes.extend(u32::MAX.to_le_bytes());
+        bytes.extend([0; 20]);
+        bytes.extend(16u32.to_le_bytes());
+        let container = HavokContainer::parse_packfile(Part::Data, 0, &bytes).unwrap();
+        assert_eq!(container.size, u32::MAX as usize + 16);
+        assert_eq!(container.sections[0].name, "__data__");
+    }
+}
@@ -0,0 +1,97 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Serialize, Serializer};
+
+use crate::index::HD2Index;
+use crate::parse::DataType;
+
+pub mod entity;
+pub mod flow;
+pub mod havok;
+pub mod mouse_cursor;
+pub mod particles;
+pub mod scan;
+pub mod scene;
+pub mod shader;
+pub mod shading_environment;
+pub mod speedtree;
+pub mod state_machine;
+pub mod texture;
+pub mod texture_atlas;
+pub mod vector_field;
+pub mod wwise;
+
+/// Read a little endian u16 at `offset`, `None` if out of bounds
+pub fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset.checked_add(2)?)?.try_into().ok()?,
+    ))
+}
+
+/// Read a little endian u32 at `offset`, `None` if out of bounds
+pub fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset.checked_add(4)?)?.try_into().ok()?,
+    ))
+}
+
+/// Read a little endian u64 at `offset`, `None` if out of bounds
+pub fn u64_at(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        bytes.get(offset..offset.checked_add(8)?)?.try_into().ok()?,
+    ))
+}
+
+/// Read a little endian f32 at `offset`, `None` if out of bounds
+pub fn f32_at(bytes: &[u8], offset: usize) -> Option<f32> {
+    u32_at(bytes, offset).map(f32::from_bits)
+}
+
+/// Read a nul terminated string at `offset`, invalid utf8 is replaced
+pub fn cstr_at(bytes: &[u8], offset: usize) -> Option<String> {
+    let tail = bytes.get(offset..)?;
+    let end = tail.iter().position(|&b| b == 0).unwrap_or(tail.len());
+    Some(String::from_utf8_lossy(&tail[..end]).into_owned())
+}
+
+pub fn write_json<T: Serialize + ?Sized>(path: impl AsRef<Path>, value: &T) {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    fs::write(path, serde_json::to_vec_pretty(value).unwrap()).unwrap();
+}
+
+/// Run `export` on every asset of `types`, in id order, printing the progress. Assets that can't be
+/// read are reported and skipped. Returns how many assets were exported.
+pub fn export_each(
+    index: &HD2Index,
+    types: &[DataType],
+    mut export: impl FnMut(u64) -> io::Result<()>,
+) -> usize {
+    let mut ids: Vec<_> = types.iter().flat_map(|&ty| index.ids_of_type(ty)).collect();
+    ids.sort();
+    let mut exported = 0;
+    for (i, &id) in ids.iter().enumerate() {
+        match export(id) {
+            Ok(()) => exported += 1,
+            Err(e) => eprintln!("Skipping {id:016x}: {e}"),
+        }
+        if i % 100 == 0 {
+            println!("Processed {i}/{}", ids.len());
+        }
+    }
+    exported
+}
+
+/// Serialize an asset id or hash as 16 hex digits, like they are printed everywhere else
+pub fn serialize_hex<S: Serializer>(id: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{id:016x}"))
+}
+
+/// Serialize a thin hash as 8 hex digits
+pub fn serialize_hex32<S: Serializer>(hash: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{hash:08x}"))
+}
@@ -0,0 +1,212 @@
+//! `mouse_cursor` assets.
+//!
+//! A cursor is an image and a hotspot. The image is either embedded as a DDS or a referenced
+//! `texture`. The hotspot position in the payload isn't known. It is taken at the first offset,
+//! after the texture reference or from the payload start for embedded images, where every cursor
+//! of the same kind holds a position inside its image, in pixels or normalized.
+
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::decode::scan::{find_refs, refs_of_type};
+use crate::decode::texture::{decode_mip, load_image, Image, TextureHeader};
+use crate::decode::{export_each, f32_at, u32_at, write_json};
+use crate::hash::Dictionary;
+use crate::index::HD2Index;
+use crate::parse::DataType;
+
+/// How far after the image we look for the hotspot
+const HOTSPOT_WINDOW: usize = 32;
+
+#[derive(Debug, Serialize)]
+pub struct MouseCursorReport {
+    pub id: String,
+    pub name: Option<String>,
+    /// Referenced texture, `None` if the image is embedded or wasn't found
+    pub texture: Option<String>,
+    pub size: Option<(u32, u32)>,
+    /// Offset and position in pixels
+    pub hotspot: Option<(usize, [u32; 2])>,
+}
+
+fn embedded_image(data: &[u8]) -> Option<Image> {
+    let offset = data.windows(4).position(|w| w == b"DDS ")?;
+    let header = TextureHeader::parse_dds(data, offset)?;
+    decode_mip(
+        header.format?,
+        header.width,
+        header.height,
+        data.get(header.data_offset..)?,
+    )
+}
+
+/// Where the hotspot is stored, relative to the start of the search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct HotspotLayout {
+    offset: usize,
+    normalized: bool,
+}
+
+impl HotspotLayout {
+    fn read(self, data: &[u8], start: usize, image: &Image) -> Option<[u32; 2]> {
+        let offset = start + self.offset;
+        if self.normalized {
+            let (u, v) = (f32_at(data, offset)?, f32_at(data, offset + 4)?);
+            // Small integers read as subnormal floats
+            let normalized = |f: f32| f == 0.0 || (f.is_normal() && (0.0..1.0).contains(&f));
+            (normalized(u) && normalized(v)).then(|| {
+                let x = (u * image.width as f32) as u32;
+                let y = (v * image.height as f32) as u32;
+                [x, y]
+            })
+        } else {
+            let (x, y) = (u32_at(data, offset)?, u32_at(data, offset + 4)?);
+            (x < image.width && y < image.height).then_some([x, y])
+        }
+    }
+}
+
+/// Every layout that gives a position inside the image
+fn hotspot_layouts(data: &[u8], start: usize, image: &Image) -> HashSet<HotspotLayout> {
+    (0..HOTSPOT_WINDOW)
+        .step_by(4)
+        .flat_map(|offset| [false, true].map(|normalized| HotspotLayout { offset, normalized }))
+        .filter(|layout| layout.read(data, start, image).is_some())
+        .collect()
+}
+
+/// The first layout that works for every cursor
+fn common_layout<'a>(
+    mut candidates: impl Iterator<Item = &'a HashSet<HotspotLayout>>,
+) -> Option<HotspotLayout> {
+    let mut common = candidates.next()?.clone();
+    for layouts in candidates {
+        common.retain(|l| layouts.contains(l));
+    }
+    common.into_iter().min_by_key(|l| (l.offset, l.normalized))
+}
+
+/// A cursor whose hotspot hasn't been located yet
+pub struct Cursor {
+    id: u64,
+    name: Option<String>,
+    texture: Option<u64>,
+    data: Vec<u8>,
+    /// Where the hotspot search starts
+    start: usize,
+    pub image: Option<Image>,
+}
+
+impl Cursor {
+    fn hotspot_layouts(&self) -> Option<HashSet<HotspotLayout>> {
+        let image = self.image.as_ref()?;
+        Some(hotspot_layouts(&self.data, self.start, image))
+    }
+
+    fn report(&self, layout: Option<HotspotLayout>) -> MouseCursorReport {
+        let hotspot = layout.zip(self.image.as_ref()).and_then(|(layout, image)| {
+            let position = layout.read(&self.data, self.start, image)?;
+            Some((self.start + layout.offset, position))
+        });
+        MouseCursorReport {
+            id: format!("{:016x}", self.id),
+            name: self.name.clone(),
+            texture: self.texture.map(|t| format!("{t:016x}")),
+            size: self.image.as_ref().map(|i| (i.width, i.height)),
+            hotspot,
+        }
+    }
+}
+
+pub fn load(index: &HD2Index, dictionary: &Dictionary, id: u64) -> io::Result<Cursor> {
+    let data = index.load_data_bytes(id)?;
+    let refs = find_refs(&data, index, dictionary);
+    let texture = refs_of_type(&refs, DataType::texture).into_iter().next();
+    // Embedded images are followed by their pixels, look from the start instead
+    let (start, image, texture) = match embedded_image(&data) {
+        Some(image) => (0, Some(image), None),
+        None => match texture {
+            Some(r) => (r.offset + 8, load_image(index, r.id), Some(r.id)),
+            None => (0, None, None),
+        },
+    };
+    Ok(Cursor {
+        id,
+        name: dictionary.get(id).map(str::to_owned),
+        texture,
+        data,
+        start,
+        image,
+    })
+}
+
+/// Export a `<asset id>.png` image and a `<asset id>.json` report for every cursor to `out_dir`
+pub fn export_all(index: &HD2Index, dictionary: &Dictionary, out_dir: impl AsRef<Path>) {
+    let out_dir = out_dir.as_ref();
+    let mut cursors = Vec::new();
+    export_each(index, &[DataType::mouse_cursor], |id| {
+        cursors.push(load(index, dictionary, id)?);
+        Ok(())
+    });
+    let candidates: Vec<_> = cursors
+        .iter()
+        .map(|c| (c.texture.is_some(), c.hotspot_layouts()))
+        .collect();
+    let layout = |referenced: bool| {
+        common_layout(
+            candidates
+                .iter()
+                .filter(|(r, _)| *r == referenced)
+                .filter_map(|(_, layouts)| layouts.as_ref()),
+        )
+    };
+    let layouts = [layout(false), layout(true)];
+    println!(
+        "Hotspot layouts, embedded: {:?}, referenced: {:?}",
+        layouts[0], layouts[1]
+    );
+    for cursor in &cursors {
+        let report = cursor.report(layouts[cursor.texture.is_some() as usize]);
+        write_json(out_dir.join(format!("{}.json", report.id)), &report);
+        if let Some(image) = &cursor.image {
+            image.write_png(out_dir.join(format!("{}.png", report.id)));
+        }
+    }
+    println!("Exported {} cursors", cursors.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decode::mouse_cursor::{common_layout, hotspot_layouts, HotspotLayout};
+    use crate::decode::texture::Image;
+
+    fn payload(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn hotspot_layout_is_shared() {
+        let image = Image {
+            width: 32,
+            height: 32,
+            pixels: Vec::new(),
+        };
+        // The hotspot is at offset 8, the top-left one included, the other values vary
+        let a = hotspot_layouts(&payload(&[5, 7, 0, 0, 99, 99, 99, 99]), 0, &image);
+        let b = hotspot_layouts(&payload(&[99, 40, 31, 4, 99, 99, 99, 99]), 0, &image);
+        let pixels = HotspotLayout {
+            offset: 8,
+            normalized: false,
+        };
+        assert_eq!(common_layout([&a, &b].into_iter()), Some(pixels));
+        assert_eq!(
+            pixels.read(&payload(&[0, 0, 0, 0]), 0, &image),
+            Some([0, 0])
+        );
+        let c = hotspot_layouts(&payload(&[1, 1, 99, 99, 99, 99, 99, 99]), 0, &image);
+        assert_eq!(common_layout([&a, &b, &c].into_iter()), None);
+    }
+}
@@ -0,0 +1,62 @@
+//! References of `particles` assets.
+//!
+//! Lists the materials, textures and other assets an effect references, the known names it holds
+//! by thin hash, and its runs of floats. Emitters, spawn rates and curves aren't decoded.
+
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::decode::scan::{
+    find_float_runs, find_refs, find_thin_names, refs_of_type, AssetRef, FloatRun, ThinName,
+};
+use crate::decode::{export_each, write_json};
+use crate::hash::Dictionary;
+use crate::index::HD2Index;
+use crate::parse::DataType;
+
+#[derive(Debug, Serialize)]
+pub struct ParticleRefs {
+    pub id: String,
+    pub name: Option<String>,
+    pub data_size: u32,
+    pub stream_size: u32,
+    pub gpu_size: u32,
+    pub materials: Vec<AssetRef>,
+    pub textures: Vec<AssetRef>,
+    /// Every resolved reference, including the materials and textures
+    pub references: Vec<AssetRef>,
+    /// Known names by thin hash
+    pub thin_names: Vec<ThinName>,
+    pub float_runs: Vec<FloatRun>,
+}
+
+pub fn decode(index: &HD2Index, dictionary: &Dictionary, id: u64) -> io::Result<ParticleRefs> {
+    let record = &index[id].record;
+    let data = index.load_data_bytes(id)?;
+    let references = find_refs(&data, index, dictionary);
+    Ok(ParticleRefs {
+        id: format!("{id:016x}"),
+        name: dictionary.get(id).map(str::to_owned),
+        data_size: record.data_size,
+        stream_size: record.stream_size,
+        gpu_size: record.gpu_size,
+        materials: refs_of_type(&references, DataType::material),
+        textures: refs_of_type(&references, DataType::texture),
+        thin_names: find_thin_names(&data, dictionary, &references),
+        references,
+        float_runs: find_float_runs(&data, 4),
+    })
+}
+
+/// Export a `<asset id>.json` reference report for every particle effect to `out_dir`
+pub fn export_all(index: &HD2Index, dictionary: &Dictionary, out_dir: impl AsRef<Path>) {
+    let out_dir = out_dir.as_ref();
+    let count = export_each(index, &[DataType::particles], |id| {
+        let report = decode(index, dictionary, id)?;
+        write_json(out_dir.join(format!("{}.json", report.id)), &report);
+        Ok(())
+    });
+    println!("Exported {count} particle effects");
+}
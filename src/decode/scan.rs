@@ -0,0 +1,174 @@
+//! Layout agnostic scans, for the asset types we haven't reversed yet.
+
+use serde::Serialize;
+
+use crate::decode::{f32_at, serialize_hex, serialize_hex32, u32_at, u64_at};
+use crate::hash::Dictionary;
+use crate::index::HD2Index;
+use crate::parse::DataType;
+
+/// A 64-bit value found in a payload that resolves to an asset or a known name
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetRef {
+    pub offset: usize,
+    #[serde(serialize_with = "serialize_hex")]
+    pub id: u64,
+    /// Type of the referenced asset if it is in the index
+    pub type_id: Option<DataType>,
+    pub name: Option<String>,
+}
+
+/// Find every 4 bytes aligned u64 that is either an asset in `index` or a hash in `dictionary`.
+pub fn find_refs(bytes: &[u8], index: &HD2Index, dictionary: &Dictionary) -> Vec<AssetRef> {
+    let mut refs = Vec::new();
+    for offset in (0..bytes.len().saturating_sub(7)).step_by(4) {
+        let value = u64_at(bytes, offset).unwrap();
+        if value == 0 {
+            continue;
+        }
+        let type_id = index.get(value).map(|e| e.record.type_id);
+        let name = dictionary.get(value).map(str::to_owned);
+        if type_id.is_some() || name.is_some() {
+            refs.push(AssetRef {
+                offset,
+                id: value,
+                type_id,
+                name,
+            });
+        }
+    }
+    refs
+}
+
+/// A 32-bit value found in a payload that is the thin hash of a known name
+#[derive(Debug, Clone, Serialize)]
+pub struct ThinName {
+    pub offset: usize,
+    #[serde(serialize_with = "serialize_hex32")]
+    pub hash: u32,
+    pub name: String,
+}
+
+/// Find every 4 bytes aligned u32 that is a thin hash in `dictionary`, outside of the 64-bit
+/// references `refs` found by [`find_refs`].
+pub fn find_thin_names(bytes: &[u8], dictionary: &Dictionary, refs: &[AssetRef]) -> Vec<ThinName> {
+    let mut refs = refs.iter().peekable();
+    (0..bytes.len().saturating_sub(3))
+        .step_by(4)
+        .filter_map(|offset| {
+            while refs.next_if(|r| r.offset + 8 <= offset).is_some() {}
+            if refs.peek().is_some_and(|r| r.offset <= offset) {
+                return None;
+            }
+            let hash = u32_at(bytes, offset).filter(|&h| h != 0)?;
+            Some(ThinName {
+                offset,
+                hash,
+                name: dictionary.get_thin(hash)?.to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Keep the references to assets of type `ty`
+pub fn refs_of_type(refs: &[AssetRef], ty: DataType) -> Vec<AssetRef> {
+    refs.iter()
+        .filter(|r| r.type_id == Some(ty))
+        .cloned()
+        .collect()
+}
+
+/// A run of consecutive plausible f32 values
+#[derive(Debug, Serialize)]
+pub struct FloatRun {
+    pub offset: usize,
+    pub values: Vec<f32>,
+}
+
+pub(crate) fn is_plausible_float(f: f32) -> bool {
+    f == 0.0 || (f.is_normal() && f.abs() > 1e-6 && f.abs() < 1e6)
+}
+
+/// Find runs of at least `min_len` 4 bytes aligned floats that aren't all zeroes
+pub fn find_float_runs(bytes: &[u8], min_len: usize) -> Vec<FloatRun> {
+    let mut runs = Vec::new();
+    let mut current = FloatRun {
+        offset: 0,
+        values: Vec::new(),
+    };
+    let mut flush = |run: &mut FloatRun, next: usize| {
+        if run.values.len() >= min_len && run.values.iter().any(|&f| f != 0.0) {
+            runs.push(std::mem::replace(
+                run,
+                FloatRun {
+                    offset: next,
+                    values: Vec::new(),
+                },
+            ));
+        } else {
+            run.offset = next;
+            run.values.clear();
+        }
+    };
+    for offset in (0..bytes.len() / 4 * 4).step_by(4) {
+        let f = f32_at(bytes, offset).unwrap();
+        if is_plausible_float(f) {
+            current.values.push(f);
+        } else {
+            flush(&mut current, offset + 4);
+        }
+    }
+    flush(&mut current, bytes.len());
+    runs
+}
+
+/// A run of printable ascii
+#[derive(Debug, Serialize)]
+pub struct FoundString {
+    pub offset: usize,
+    pub value: String,
+}
+
+/// Find runs of at least `min_len` printable ascii characters
+pub fn find_strings(bytes: &[u8], min_len: usize) -> Vec<FoundString> {
+    let mut strings = Vec::new();
+    let mut start = 0;
+    for (i, &b) in bytes.iter().chain([&0]).enumerate() {
+        if !(b.is_ascii_graphic() || b == b' ') {
+            if i - start >= min_len {
+                strings.push(FoundString {
+                    offset: start,
+                    value: String::from_utf8_lossy(&bytes[start..i]).into_owned(),
+                });
+            }
+            start = i + 1;
+        }
+    }
+    strings
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decode::scan::{find_thin_names, AssetRef};
+    use crate::hash::{stingray_hash, stingray_thin_hash, Dictionary};
+
+    #[test]
+    fn thin_names_skip_64_bit_refs() {
+        let mut dictionary = Dictionary::default();
+        dictionary.add_names("test", 0, ["content/ui/icon"]);
+        let full = stingray_hash(b"content/ui/icon");
+        let thin = stingray_thin_hash(b"content/ui/icon");
+        let mut bytes = full.to_le_bytes().to_vec();
+        bytes.extend(thin.to_le_bytes());
+        let refs = [AssetRef {
+            offset: 0,
+            id: full,
+            type_id: None,
+            name: Some("content/ui/icon".to_owned()),
+        }];
+        let names = find_thin_names(&bytes, &dictionary, &refs);
+        assert_eq!(names.len(), 1);
+        assert_eq!(names[0].offset, 8);
+        assert_eq!(find_thin_names(&bytes, &dictionary, &[]).len(), 2);
+    }
+}
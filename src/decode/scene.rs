@@ -0,0 +1,168 @@
+//! `level` and `prefab` assets.
+//!
+//! Placements are the unit and prefab references, each with the transform stored near it: a
+//! normalized quaternion with plausible position and scale vectors around it. Nested prefabs are
+//! followed. Transforms found this way are guesses, check them before relying on them.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::decode::scan::{find_refs, AssetRef};
+use crate::decode::{export_each, f32_at, write_json};
+use crate::hash::Dictionary;
+use crate::index::HD2Index;
+use crate::parse::DataType;
+
+/// How far after a reference we look for its transform
+const TRANSFORM_WINDOW: usize = 64;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TransformGuess {
+    /// Offset of the position in the payload
+    pub offset: usize,
+    pub position: [f32; 3],
+    /// x, y, z, w
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Placement {
+    pub asset: AssetRef,
+    pub transform: Option<TransformGuess>,
+    /// Content of the prefab if `asset` is one, empty if the prefab includes itself
+    pub children: Vec<Placement>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SceneReport {
+    pub id: String,
+    pub name: Option<String>,
+    pub type_id: DataType,
+    pub placements: Vec<Placement>,
+    /// Resolved references that are neither units nor prefabs
+    pub other_references: Vec<AssetRef>,
+}
+
+fn vec_at<const N: usize>(bytes: &[u8], offset: usize) -> Option<[f32; N]> {
+    let mut v = [0.0; N];
+    for (i, f) in v.iter_mut().enumerate() {
+        *f = f32_at(bytes, offset + i * 4).filter(|f| f.is_finite() && f.abs() < 1e5)?;
+    }
+    Some(v)
+}
+
+/// Look for `position, rotation, scale` after `start`
+fn find_transform(bytes: &[u8], start: usize) -> Option<TransformGuess> {
+    (start..start + TRANSFORM_WINDOW)
+        .step_by(4)
+        .find_map(|offset| {
+            let position = vec_at::<3>(bytes, offset)?;
+            let rotation = vec_at::<4>(bytes, offset + 12)?;
+            let scale = vec_at::<3>(bytes, offset + 28)?;
+            let norm = rotation.iter().map(|f| f * f).sum::<f32>().sqrt();
+            let plausible_scale = scale.iter().all(|&s| s > 1e-3 && s < 1e3);
+            ((norm - 1.0).abs() < 1e-3 && plausible_scale).then_some(TransformGuess {
+                offset,
+                position,
+                rotation,
+                scale,
+            })
+        })
+}
+
+/// Prefabs expanded while decoding a scene, so that each of them is read once
+#[derive(Default)]
+struct Expansion {
+    children: HashMap<u64, Vec<Placement>>,
+    /// Prefabs being expanded, from the scene down
+    ancestors: Vec<u64>,
+    /// Times a prefab wasn't expanded because it is its own ancestor. Children that went through
+    /// such a cut depend on their ancestors, they aren't memoized.
+    cuts: usize,
+}
+
+fn prefab_children(
+    index: &HD2Index,
+    dictionary: &Dictionary,
+    id: u64,
+    expansion: &mut Expansion,
+) -> Vec<Placement> {
+    if expansion.ancestors.contains(&id) {
+        expansion.cuts += 1;
+        return Vec::new();
+    }
+    if let Some(children) = expansion.children.get(&id) {
+        return children.clone();
+    }
+    let cuts = expansion.cuts;
+    let children = match collect_placements(index, dictionary, id, expansion) {
+        Ok((children, _)) => children,
+        Err(e) => {
+            eprintln!("Prefab {id:016x} not expanded: {e}");
+            return Vec::new();
+        }
+    };
+    if expansion.cuts == cuts {
+        expansion.children.insert(id, children.clone());
+    }
+    children
+}
+
+fn collect_placements(
+    index: &HD2Index,
+    dictionary: &Dictionary,
+    id: u64,
+    expansion: &mut Expansion,
+) -> io::Result<(Vec<Placement>, Vec<AssetRef>)> {
+    let data = index.load_data_bytes(id)?;
+    let mut placements = Vec::new();
+    let mut others = Vec::new();
+    expansion.ancestors.push(id);
+    for r in find_refs(&data, index, dictionary) {
+        match r.type_id {
+            Some(DataType::unit) => placements.push(Placement {
+                transform: find_transform(&data, r.offset + 8),
+                asset: r,
+                children: Vec::new(),
+            }),
+            Some(DataType::prefab) => {
+                let children = prefab_children(index, dictionary, r.id, expansion);
+                placements.push(Placement {
+                    transform: find_transform(&data, r.offset + 8),
+                    asset: r,
+                    children,
+                })
+            }
+            _ => others.push(r),
+        }
+    }
+    expansion.ancestors.pop();
+    Ok((placements, others))
+}
+
+pub fn decode(index: &HD2Index, dictionary: &Dictionary, id: u64) -> io::Result<SceneReport> {
+    let (placements, other_references) =
+        collect_placements(index, dictionary, id, &mut Expansion::default())?;
+    Ok(SceneReport {
+        id: format!("{id:016x}"),
+        name: dictionary.get(id).map(str::to_owned),
+        type_id: index[id].record.type_id,
+        placements,
+        other_references,
+    })
+}
+
+/// Export a `<asset id>.json` placement list for every level and prefab to `out_dir`
+pub fn export_all(index: &HD2Index, dictionary: &Dictionary, out_dir: impl AsRef<Path>) {
+    let out_dir = out_dir.as_ref();
+    let count = export_each(index, &[DataType::level, DataType::prefab], |id| {
+        let report = decode(index, dictionary, id)?;
+        write_json(out_dir.join(format!("{}.json", report.id)), &report);
+        Ok(())
+    });
+    println!("Exported {count} scenes");
+}
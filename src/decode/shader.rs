@@ -0,0 +1,797 @@
+//! `shader_library` and `shader_library_group` assets.
+//!
+//! The library layout itself isn't reversed yet, but it embeds plain DirectX shader containers
+//! (DXBC for SM <= 5.1, DXIL for SM 6+). We locate them by their magic and reflect what the
+//! container parts tell us, stage included.
+//!
+//! The permutation table is found by the container sizes its records hold, one record per shader
+//! at a fixed stride. Only the size and offset fields are named, the other words of each record
+//! are exported as they are, the permutation keys are among those that differ between records.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use binrw::BinRead;
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use crate::decode::{cstr_at, export_each, u16_at, u32_at, write_json};
+use crate::index::{HD2Index, Part};
+use crate::parse::DataType;
+
+const DXBC_MAGIC: &[u8; 4] = b"DXBC";
+
+#[derive(BinRead, Debug)]
+#[br(little, magic = b"DXBC")]
+pub struct DxContainerHeader {
+    pub digest: [u8; 16],
+    pub major_version: u16,
+    pub minor_version: u16,
+    /// Size of the whole container, header included
+    pub size: u32,
+    pub part_count: u32,
+    #[br(count = part_count)]
+    pub part_offsets: Vec<u32>,
+}
+
+#[derive(Debug)]
+pub struct DxPart<'a> {
+    pub fourcc: [u8; 4],
+    pub data: &'a [u8],
+}
+
+impl DxPart<'_> {
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.fourcc).into_owned()
+    }
+}
+
+#[derive(Debug)]
+pub struct DxContainer<'a> {
+    pub header: DxContainerHeader,
+    pub parts: Vec<DxPart<'a>>,
+}
+
+impl<'a> DxContainer<'a> {
+    /// Parse a container from the start of `bytes`, trailing bytes are ignored
+    pub fn parse(bytes: &'a [u8]) -> Option<Self> {
+        let header = DxContainerHeader::read(&mut Cursor::new(bytes)).ok()?;
+        let bytes = bytes.get(..header.size as usize)?;
+        let mut parts = Vec::with_capacity(header.part_count as usize);
+        for &offset in &header.part_offsets {
+            let offset = offset as usize;
+            let fourcc = bytes.get(offset..offset + 4)?.try_into().ok()?;
+            let size = u32_at(bytes, offset + 4)? as usize;
+            let data = bytes.get(offset + 8..offset + 8 + size)?;
+            parts.push(DxPart { fourcc, data });
+        }
+        Some(Self { header, parts })
+    }
+
+    pub fn part(&self, fourcc: &[u8; 4]) -> Option<&DxPart<'a>> {
+        self.parts.iter().find(|p| &p.fourcc == fourcc)
+    }
+
+    pub fn is_dxil(&self) -> bool {
+        self.part(b"DXIL").is_some()
+    }
+}
+
+/// Find all the shader containers in an asset payload. Returns their offset and bytes.
+pub fn find_containers(bytes: &[u8]) -> Vec<(usize, &[u8])> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(hit) = bytes[pos..].windows(4).position(|w| w == DXBC_MAGIC) {
+        let start = pos + hit;
+        if looks_like_container(bytes, start) {
+            let size = u32_at(bytes, start + 24).unwrap() as usize;
+            found.push((start, &bytes[start..start + size]));
+            pos = start + size;
+        } else {
+            pos = start + 4;
+        }
+    }
+    found
+}
+
+/// Check whether the header of a container starting at `offset` is plausible
+pub fn looks_like_container(bytes: &[u8], offset: usize) -> bool {
+    bytes.get(offset..offset + 4) == Some(DXBC_MAGIC)
+        && u16_at(bytes, offset + 20) == Some(1)
+        && u32_at(bytes, offset + 24).is_some_and(|s| s >= 32 && offset + s as usize <= bytes.len())
+}
+
+pub fn stage_name(program_type: u16) -> &'static str {
+    match program_type {
+        0 => "pixel",
+        1 => "vertex",
+        2 => "geometry",
+        3 => "hull",
+        4 => "domain",
+        5 => "compute",
+        6 => "library",
+        7 => "ray_generation",
+        8 => "intersection",
+        9 => "any_hit",
+        10 => "closest_hit",
+        11 => "miss",
+        12 => "callable",
+        13 => "mesh",
+        14 => "amplification",
+        _ => "unknown",
+    }
+}
+
+/// D3D_SHADER_INPUT_TYPE
+fn input_type_name(ty: u32) -> &'static str {
+    match ty {
+        0 => "cbuffer",
+        1 => "tbuffer",
+        2 => "texture",
+        3 => "sampler",
+        4 => "uav_rwtyped",
+        5 => "structured",
+        6 => "uav_rwstructured",
+        7 => "byteaddress",
+        8 => "uav_rwbyteaddress",
+        9 => "uav_append_structured",
+        10 => "uav_consume_structured",
+        11 => "uav_rwstructured_with_counter",
+        12 => "rt_acceleration_structure",
+        13 => "uav_feedback_texture",
+        _ => "unknown",
+    }
+}
+
+/// D3D_SRV_DIMENSION
+fn dimension_name(dim: u32) -> &'static str {
+    match dim {
+        1 => "buffer",
+        2 => "texture1d",
+        3 => "texture1darray",
+        4 => "texture2d",
+        5 => "texture2darray",
+        6 => "texture2dms",
+        7 => "texture2dmsarray",
+        8 => "texture3d",
+        9 => "texturecube",
+        10 => "texturecubearray",
+        11 => "bufferex",
+        _ => "unknown",
+    }
+}
+
+/// PSVResourceType from DxilPipelineStateValidation.h
+fn psv_resource_type_name(ty: u32) -> &'static str {
+    match ty {
+        1 => "sampler",
+        2 => "cbuffer",
+        3 => "srv_typed",
+        4 => "srv_raw",
+        5 => "srv_structured",
+        6 => "uav_typed",
+        7 => "uav_raw",
+        8 => "uav_structured",
+        9 => "uav_structured_with_counter",
+        _ => "unknown",
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResourceBinding {
+    pub name: Option<String>,
+    pub kind: &'static str,
+    pub dimension: Option<&'static str>,
+    pub space: u32,
+    pub bind_point: u32,
+    pub bind_count: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConstantBuffer {
+    pub name: String,
+    pub size: u32,
+    pub variables: Vec<Variable>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Variable {
+    pub name: String,
+    pub offset: u32,
+    pub size: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SignatureElement {
+    pub semantic: String,
+    pub semantic_index: u32,
+    pub system_value: u32,
+    pub component_type: u32,
+    pub register: u32,
+    pub mask: u8,
+    pub stream: u32,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Reflection {
+    pub stage: Option<&'static str>,
+    pub shader_model: Option<String>,
+    pub creator: Option<String>,
+    pub resources: Vec<ResourceBinding>,
+    pub constant_buffers: Vec<ConstantBuffer>,
+    pub inputs: Vec<SignatureElement>,
+    pub outputs: Vec<SignatureElement>,
+    pub patch_constants: Vec<SignatureElement>,
+    /// Size of the SHEX/SHDR token stream or the DXIL bitcode
+    pub program_size: Option<u32>,
+}
+
+impl Reflection {
+    pub fn new(container: &DxContainer) -> Self {
+        let mut refl = Self::default();
+        for part in &container.parts {
+            match &part.fourcc {
+                b"RDEF" => refl.parse_rdef(part.data),
+                b"SHEX" | b"SHDR" => refl.parse_program(part.data),
+                b"DXIL" => refl.parse_dxil(part.data),
+                b"PSV0" if refl.resources.is_empty() => refl.parse_psv0(part.data),
+                b"ISGN" | b"ISG1" => refl.inputs = parse_signature(&part.fourcc, part.data),
+                b"OSGN" | b"OSG1" | b"OSG5" => {
+                    refl.outputs = parse_signature(&part.fourcc, part.data)
+                }
+                b"PCSG" | b"PSG1" => {
+                    refl.patch_constants = parse_signature(&part.fourcc, part.data)
+                }
+                _ => {}
+            }
+        }
+        refl
+    }
+
+    fn set_version(&mut self, version: u32) {
+        self.stage = Some(stage_name((version >> 16) as u16));
+        self.shader_model = Some(format!("{}.{}", (version >> 4) & 0xf, version & 0xf));
+    }
+
+    fn parse_program(&mut self, data: &[u8]) {
+        if let (Some(version), Some(len)) = (u32_at(data, 0), u32_at(data, 4)) {
+            self.set_version(version);
+            self.program_size = Some(len * 4);
+        }
+    }
+
+    fn parse_dxil(&mut self, data: &[u8]) {
+        let Some(version) = u32_at(data, 0) else {
+            return;
+        };
+        self.set_version(version);
+        self.program_size = u32_at(data, 20);
+    }
+
+    fn parse_rdef(&mut self, data: &[u8]) {
+        let (Some(cb_count), Some(cb_offset), Some(rb_count), Some(rb_offset)) = (
+            u32_at(data, 0),
+            u32_at(data, 4),
+            u32_at(data, 8),
+            u32_at(data, 12),
+        ) else {
+            return;
+        };
+        let minor = data.get(16).copied().unwrap_or(0);
+        let major = data.get(17).copied().unwrap_or(0);
+        self.creator = u32_at(data, 24).and_then(|o| cstr_at(data, o as usize));
+        // SM 5.1 added register spaces to the bindings, SM 5 extended the variables
+        let sm51 = major > 5 || (major == 5 && minor >= 1);
+        let binding_size = if sm51 { 40 } else { 32 };
+        let variable_size = if major >= 5 { 40 } else { 24 };
+
+        for i in 0..rb_count as usize {
+            let base = rb_offset as usize + i * binding_size;
+            let field = |n: usize| u32_at(data, base + n * 4);
+            let (Some(name), Some(ty), Some(dim), Some(bind_point), Some(bind_count)) =
+                (field(0), field(1), field(3), field(5), field(6))
+            else {
+                break;
+            };
+            self.resources.push(ResourceBinding {
+                name: cstr_at(data, name as usize),
+                kind: input_type_name(ty),
+                dimension: Some(dimension_name(dim)),
+                space: if sm51 { field(8).unwrap_or(0) } else { 0 },
+                bind_point,
+                bind_count,
+            });
+        }
+
+        for i in 0..cb_count as usize {
+            let base = cb_offset as usize + i * 24;
+            let (Some(name), Some(var_count), Some(var_offset), Some(size)) = (
+                u32_at(data, base),
+                u32_at(data, base + 4),
+                u32_at(data, base + 8),
+                u32_at(data, base + 12),
+            ) else {
+                break;
+            };
+            let variables = (0..var_count as usize)
+                .map_while(|j| {
+                    let base = var_offset as usize + j * variable_size;
+                    Some(Variable {
+                        name: cstr_at(data, u32_at(data, base)? as usize)?,
+                        offset: u32_at(data, base + 4)?,
+                        size: u32_at(data, base + 8)?,
+                    })
+                })
+                .collect();
+            self.constant_buffers.push(ConstantBuffer {
+                name: cstr_at(data, name as usize).unwrap_or_default(),
+                size,
+                variables,
+            });
+        }
+    }
+
+    fn parse_psv0(&mut self, data: &[u8]) {
+        let Some(info_size) = u32_at(data, 0) else {
+            return;
+        };
+        let base = 4 + info_size as usize;
+        let Some(count) = u32_at(data, base) else {
+            return;
+        };
+        if count == 0 {
+            return;
+        }
+        let Some(entry_size) = u32_at(data, base + 4) else {
+            return;
+        };
+        for i in 0..count as usize {
+            let entry = base + 8 + i * entry_size as usize;
+            let field = |n: usize| u32_at(data, entry + n * 4);
+            let (Some(ty), Some(space), Some(lower), Some(upper)) =
+                (field(0), field(1), field(2), field(3))
+            else {
+                break;
+            };
+            self.resources.push(ResourceBinding {
+                name: None,
+                kind: psv_resource_type_name(ty),
+                dimension: None,
+                space,
+                bind_point: lower,
+                bind_count: upper.wrapping_sub(lower).wrapping_add(1),
+            });
+        }
+    }
+}
+
+fn parse_signature(fourcc: &[u8; 4], data: &[u8]) -> Vec<SignatureElement> {
+    // ISGN/OSGN/PCSG elements are 24 bytes, OSG5 prepends the stream index, the SM 5.1
+    // variants (*SG1) also append the min precision.
+    let (size, stream_first) = match fourcc {
+        b"OSG5" => (28, true),
+        b"ISG1" | b"OSG1" | b"PSG1" => (32, true),
+        _ => (24, false),
+    };
+    let (Some(count), Some(offset)) = (u32_at(data, 0), u32_at(data, 4)) else {
+        return Vec::new();
+    };
+    (0..count as usize)
+        .map_while(|i| {
+            let mut base = offset as usize + i * size;
+            let stream = if stream_first {
+                base += 4;
+                u32_at(data, base - 4)?
+            } else {
+                0
+            };
+            Some(SignatureElement {
+                semantic: cstr_at(data, u32_at(data, base)? as usize)?,
+                semantic_index: u32_at(data, base + 4)?,
+                system_value: u32_at(data, base + 8)?,
+                component_type: u32_at(data, base + 12)?,
+                register: u32_at(data, base + 16)?,
+                mask: *data.get(base + 20)?,
+                stream,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShaderReport {
+    /// Position of this shader in the library, used as file name for the extracted blob
+    pub index: usize,
+    pub part: Part,
+    pub offset: usize,
+    pub size: u32,
+    pub format: &'static str,
+    pub parts: Vec<String>,
+    #[serde(flatten)]
+    pub reflection: Reflection,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShaderLibraryReport {
+    pub id: String,
+    pub type_id: DataType,
+    /// Number of shaders per stage, `unknown` if the container doesn't say
+    pub stages: BTreeMap<&'static str, usize>,
+    pub permutations: Option<PermutationTable>,
+    pub shaders: Vec<ShaderReport>,
+}
+
+/// Longest record looked for in a permutation table
+const MAX_RECORD_SIZE: usize = 256;
+
+/// Records at a fixed stride, the `k`th one holding the size of the `k`th shader
+#[derive(Debug, Serialize)]
+pub struct PermutationTable {
+    pub part: Part,
+    /// Offset of the first record
+    pub offset: usize,
+    pub record_size: usize,
+    /// Offsets in a record of the container size and, when found, of the container offset
+    pub size_field: usize,
+    pub offset_field: Option<usize>,
+    /// Offsets in a record of the other words that differ between records
+    pub varying: Vec<usize>,
+    /// Words of each record, in shader order
+    pub records: Vec<Vec<String>>,
+}
+
+/// Find the permutation table of `containers`, their offset and size in shader order, in `bytes`.
+///
+/// The stride is the distance between the first two sizes. Where records start isn't known: right
+/// after a shader count within one record before the first size if there is one, at the container
+/// offset field if it comes before the size, at the size field otherwise.
+pub fn find_permutation_table(
+    part: Part,
+    bytes: &[u8],
+    containers: &[(usize, u32)],
+) -> Option<PermutationTable> {
+    let [(_, first), (_, second), ..] = *containers else {
+        return None;
+    };
+    let word = |offset: usize| u32_at(bytes, offset);
+    let holds_sizes = |start: usize, stride: usize| {
+        containers
+            .iter()
+            .enumerate()
+            .all(|(k, &(_, size))| word(start + k * stride) == Some(size))
+    };
+    let (start, stride) = (0..bytes.len().saturating_sub(3))
+        .step_by(4)
+        .filter(|&start| word(start) == Some(first))
+        .find_map(|start| {
+            (4..=MAX_RECORD_SIZE)
+                .step_by(4)
+                .filter(|&stride| word(start + stride) == Some(second))
+                .find(|&stride| holds_sizes(start, stride))
+                .map(|stride| (start, stride))
+        })?;
+
+    // Column, relative to the size, whose values move like the container offsets
+    let count = containers.len();
+    let column = |relative: isize| -> Option<Vec<u32>> {
+        (0..count)
+            .map(|k| word(start.checked_add_signed(relative)? + k * stride))
+            .collect()
+    };
+    let offset_column = (1..stride as isize / 4)
+        .flat_map(|i| [-4 * i, 4 * i])
+        .find(|&relative| {
+            column(relative).is_some_and(|values| {
+                let moved = |&(offset, _): &(usize, u32)| offset.wrapping_sub(containers[0].0);
+                let moves = |&value: &u32| value.wrapping_sub(values[0]) as usize;
+                containers.iter().map(moved).eq(values.iter().map(moves))
+            })
+        });
+    let count_before = (1..=stride / 4)
+        .map(|i| start.checked_sub(4 * i))
+        .take_while(Option::is_some)
+        .flatten()
+        .find(|&at| word(at) == Some(count as u32));
+    let offset = match (count_before, offset_column) {
+        (Some(at), _) => at + 4,
+        (None, Some(relative)) if relative < 0 => start - relative.unsigned_abs(),
+        _ => start,
+    };
+    let size_field = start - offset;
+    let offset_field = offset_column.and_then(|relative| size_field.checked_add_signed(relative));
+
+    let records: Vec<Vec<u32>> = (0..count)
+        .map(|k| {
+            (0..stride)
+                .step_by(4)
+                .map(|at| word(offset + k * stride + at))
+                .collect()
+        })
+        .collect::<Option<_>>()?;
+    let varying = (0..stride)
+        .step_by(4)
+        .filter(|&at| at != size_field && Some(at) != offset_field)
+        .filter(|&at| records.iter().any(|r| r[at / 4] != records[0][at / 4]))
+        .collect();
+    Some(PermutationTable {
+        part,
+        offset,
+        record_size: stride,
+        size_field,
+        offset_field,
+        varying,
+        records: records
+            .iter()
+            .map(|r| r.iter().map(|w| format!("{w:08x}")).collect())
+            .collect(),
+    })
+}
+
+/// Locate and reflect every shader container in an asset
+pub fn reflect_asset(index: &HD2Index, id: u64) -> (ShaderLibraryReport, Vec<Vec<u8>>) {
+    let mut shaders = Vec::new();
+    let mut blobs = Vec::new();
+    let mut parts = Vec::new();
+    for part in Part::iter() {
+        let Ok(bytes) = index.load_part_bytes(id, part) else {
+            continue;
+        };
+        for (offset, blob) in find_containers(&bytes) {
+            let Some(container) = DxContainer::parse(blob) else {
+                continue;
+            };
+            shaders.push(ShaderReport {
+                index: shaders.len(),
+                part,
+                offset,
+                size: container.header.size,
+                format: if container.is_dxil() { "dxil" } else { "dxbc" },
+                parts: container.parts.iter().map(DxPart::name).collect(),
+                reflection: Reflection::new(&container),
+            });
+            blobs.push(blob.to_vec());
+        }
+        parts.push((part, bytes));
+    }
+    let containers: Vec<_> = shaders.iter().map(|s| (s.offset, s.size)).collect();
+    let permutations = parts
+        .iter()
+        .find_map(|(part, bytes)| find_permutation_table(*part, bytes, &containers));
+    let mut stages = BTreeMap::new();
+    for shader in &shaders {
+        *stages
+            .entry(shader.reflection.stage.unwrap_or("unknown"))
+            .or_default() += 1;
+    }
+    let report = ShaderLibraryReport {
+        id: format!("{id:016x}"),
+        type_id: index[id].record.type_id,
+        stages,
+        permutations,
+        shaders,
+    };
+    (report, blobs)
+}
+
+/// Extract every shader blob of every shader library to `out_dir/<asset id>/` along with a
+/// `reflection.json` report.
+pub fn extract_all(index: &HD2Index, out_dir: impl AsRef<Path>) {
+    let out_dir = out_dir.as_ref();
+    let mut total = 0;
+    let types = [DataType::shader_library, DataType::shader_library_group];
+    let count = export_each(index, &types, |id| {
+        let (report, blobs) = reflect_asset(index, id);
+        let dir = out_dir.join(&report.id);
+        fs::create_dir_all(&dir).unwrap();
+        for (shader, blob) in report.shaders.iter().zip(&blobs) {
+            fs::write(
+                dir.join(format!("{:04}.{}", shader.index, shader.format)),
+                blob,
+            )
+            .unwrap();
+        }
+        total += blobs.len();
+        write_json(dir.join("reflection.json"), &report);
+        Ok(())
+    });
+    println!("Extracted {total} shaders from {count} libraries");
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decode::shader::{
+        find_containers, find_permutation_table, parse_signature, DxContainer, Reflection,
+    };
+    use crate::index::Part;
+
+    fn words(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    /// A container of `parts`, each a fourcc and its data
+    fn container(parts: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let header_size = 32 + parts.len() * 4;
+        let mut offsets = Vec::new();
+        let mut body = Vec::new();
+        for (fourcc, data) in parts {
+            offsets.push((header_size + body.len()) as u32);
+            body.extend(*fourcc);
+            body.extend(words(&[data.len() as u32]));
+            body.extend(data);
+        }
+        let mut bytes = b"DXBC".to_vec();
+        bytes.extend([0; 16]);
+        bytes.extend(words(&[
+            1,
+            (header_size + body.len()) as u32,
+            parts.len() as u32,
+        ]));
+        bytes.extend(words(&offsets));
+        bytes.extend(body);
+        bytes
+    }
+
+    #[test]
+    fn container_parts() {
+        let mut bytes = container(&[(b"SHEX", words(&[0x50, 2])), (b"STAT", vec![7; 3])]);
+        // Trailing bytes aren't part of the container
+        bytes.extend([0xff; 8]);
+        let container = DxContainer::parse(&bytes).unwrap();
+        assert_eq!(container.header.major_version, 1);
+        assert_eq!(container.parts.len(), 2);
+        assert_eq!(container.part(b"STAT").unwrap().data, &[7; 3]);
+        assert!(!container.is_dxil());
+        let reflection = Reflection::new(&container);
+        assert_eq!(reflection.stage, Some("pixel"));
+        assert_eq!(reflection.shader_model.as_deref(), Some("5.0"));
+        assert_eq!(reflection.program_size, Some(8));
+        assert!(DxContainer::parse(&bytes[..40]).is_none());
+    }
+
+    #[test]
+    fn permutation_table() {
+        let blobs: Vec<_> = (1..=3)
+            .map(|n| container(&[(b"STAT", vec![0; n * 4])]))
+            .collect();
+        // A header, the shader count, records of key, offset, size and flags, then the shaders
+        let table = 16 + 4;
+        let mut offset = table + 3 * 24;
+        let mut bytes = vec![0xee; 16];
+        bytes.extend(words(&[3]));
+        for (key, blob) in [0x1111_0001u32, 0x2222_0002, 0x3333_0003]
+            .iter()
+            .zip(&blobs)
+        {
+            bytes.extend(words(&[*key, 0, offset as u32, blob.len() as u32, 1, 0]));
+            offset += blob.len();
+        }
+        for blob in &blobs {
+            bytes.extend(blob);
+        }
+
+        let containers: Vec<_> = find_containers(&bytes)
+            .into_iter()
+            .map(|(offset, blob)| (offset, blob.len() as u32))
+            .collect();
+        assert_eq!(containers.len(), 3);
+        let found = find_permutation_table(Part::Data, &bytes, &containers).unwrap();
+        assert_eq!(found.offset, table);
+        assert_eq!(found.record_size, 24);
+        assert_eq!(found.size_field, 12);
+        assert_eq!(found.offset_field, Some(8));
+        assert_eq!(found.varying, [0]);
+        assert_eq!(found.records[1][0], "22220002");
+        assert!(find_permutation_table(Part::Data, &bytes, &containers[..1]).is_none());
+    }
+
+    /// An RDEF part with 2 textures and a cbuffer of 2 variables, laid out for SM `major.minor`
+    fn rdef(major: u8, minor: u8) -> Vec<u8> {
+        let sm51 = (major, minor) >= (5, 1);
+        let binding_size = if sm51 { 40 } else { 32 };
+        let variable_size = if major >= 5 { 40 } else { 24 };
+        let bindings = 28;
+        let cbuffers = bindings + 2 * binding_size;
+        let variables = cbuffers + 24;
+        let strings = variables + 2 * variable_size;
+        let names = [
+            "t_albedo\0",
+            "t_normal\0",
+            "c_material\0",
+            "tint\0",
+            "roughness\0",
+        ];
+        let name_offsets: Vec<u32> = names
+            .iter()
+            .scan(strings, |at, name| {
+                let offset = *at;
+                *at += name.len();
+                Some(offset as u32)
+            })
+            .collect();
+
+        let mut data = words(&[1, cbuffers as u32, 2, bindings as u32]);
+        data.extend([minor, major]);
+        data.extend(0xffffu16.to_le_bytes());
+        data.extend(words(&[0, name_offsets[0]]));
+        for (i, name) in name_offsets[..2].iter().enumerate() {
+            // name, texture, return type, texture2d, samples, bind point, count, flags
+            let mut binding = vec![*name, 2, 5, 4, 0xffffffff, i as u32 + 3, 1, 0];
+            if sm51 {
+                binding.extend([i as u32 + 1, i as u32]);
+            }
+            data.extend(words(&binding));
+        }
+        data.extend(words(&[name_offsets[2], 2, variables as u32, 32, 0, 0]));
+        for (i, name) in name_offsets[3..].iter().enumerate() {
+            let mut variable = vec![*name, i as u32 * 16, 16, 2, 0, 0];
+            variable.resize(variable_size / 4, 0xffffffff);
+            data.extend(words(&variable));
+        }
+        for name in names {
+            data.extend(name.as_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn rdef_layouts() {
+        for (major, minor) in [(5, 0), (5, 1)] {
+            let mut reflection = Reflection::default();
+            reflection.parse_rdef(&rdef(major, minor));
+            let sm51 = minor == 1;
+            let normal = &reflection.resources[1];
+            assert_eq!(normal.name.as_deref(), Some("t_normal"));
+            assert_eq!(normal.kind, "texture");
+            assert_eq!(normal.dimension, Some("texture2d"));
+            assert_eq!(normal.bind_point, 4);
+            assert_eq!(normal.space, if sm51 { 2 } else { 0 });
+            let cbuffer = &reflection.constant_buffers[0];
+            assert_eq!(cbuffer.name, "c_material");
+            assert_eq!(cbuffer.size, 32);
+            let roughness = &cbuffer.variables[1];
+            assert_eq!(roughness.name, "roughness");
+            assert_eq!((roughness.offset, roughness.size), (16, 16));
+        }
+    }
+
+    /// A signature of POSITION0 in register 0 and TEXCOORD1 in register 1, with elements of
+    /// `size` bytes, the stream index first if `stream`
+    fn signature(size: usize, stream: bool) -> Vec<u8> {
+        let strings = 8 + 2 * size;
+        let mut data = words(&[2, 8]);
+        for (i, name) in [strings, strings + 9].into_iter().enumerate() {
+            let mut element = Vec::new();
+            if stream {
+                element.push(i as u32 + 1);
+            }
+            // name, index, system value, component type, register, mask
+            element.extend([name as u32, i as u32, 1 - i as u32, 3, i as u32, 0x0f]);
+            element.resize(size / 4, 0);
+            data.extend(words(&element));
+        }
+        data.extend(b"POSITION\0TEXCOORD\0");
+        data
+    }
+
+    #[test]
+    fn signature_layouts() {
+        for (fourcc, size, stream) in [
+            (b"ISGN", 24, false),
+            (b"OSG5", 28, true),
+            (b"ISG1", 32, true),
+            (b"PSG1", 32, true),
+        ] {
+            let elements = parse_signature(fourcc, &signature(size, stream));
+            assert_eq!(elements.len(), 2);
+            let texcoord = &elements[1];
+            assert_eq!(texcoord.semantic, "TEXCOORD");
+            assert_eq!(texcoord.semantic_index, 1);
+            assert_eq!(texcoord.system_value, 0);
+            assert_eq!(texcoord.register, 1);
+            assert_eq!(texcoord.mask, 0x0f);
+            assert_eq!(texcoord.stream, if stream { 2 } else { 0 });
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! `shading_environment` and `shading_environment_mapping` assets.
+//!
+//! Settings (fog, exposure, sky...) are keyed by hashed names. Every known name is reported with
+//! the floats that follow it, scalar and vector settings alike. Textures are resolved through the
+//! index.
+
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::decode::scan::{
+    find_refs, find_thin_names, is_plausible_float, refs_of_type, AssetRef, ThinName,
+};
+use crate::decode::{export_each, f32_at, serialize_hex, write_json};
+use crate::hash::Dictionary;
+use crate::index::HD2Index;
+use crate::parse::DataType;
+
+#[derive(Debug, Serialize)]
+pub struct Setting {
+    pub offset: usize,
+    #[serde(serialize_with = "serialize_hex")]
+    pub key: u64,
+    pub name: String,
+    /// Up to 4 plausible floats following the key
+    pub values: Vec<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShadingEnvironmentReport {
+    pub id: String,
+    pub name: Option<String>,
+    pub type_id: DataType,
+    pub settings: Vec<Setting>,
+    pub textures: Vec<AssetRef>,
+    /// Known names by thin hash, material parameters
+    pub thin_names: Vec<ThinName>,
+    /// Every resolved asset reference, environments for a mapping
+    pub references: Vec<AssetRef>,
+}
+
+pub fn decode(
+    index: &HD2Index,
+    dictionary: &Dictionary,
+    id: u64,
+) -> io::Result<ShadingEnvironmentReport> {
+    let data = index.load_data_bytes(id)?;
+    let refs = find_refs(&data, index, dictionary);
+    let thin_names = find_thin_names(&data, dictionary, &refs);
+    let (references, names): (Vec<_>, Vec<_>) = refs.into_iter().partition(|r| r.type_id.is_some());
+    let settings = names
+        .into_iter()
+        .map(|r| Setting {
+            values: (0..4)
+                .map_while(|i| f32_at(&data, r.offset + 8 + i * 4))
+                .take_while(|&f| is_plausible_float(f))
+                .collect(),
+            offset: r.offset,
+            key: r.id,
+            name: r.name.unwrap_or_default(),
+        })
+        .collect();
+    Ok(ShadingEnvironmentReport {
+        id: format!("{id:016x}"),
+        name: dictionary.get(id).map(str::to_owned),
+        type_id: index[id].record.type_id,
+        settings,
+        textures: refs_of_type(&references, DataType::texture),
+        thin_names,
+        references,
+    })
+}
+
+/// Export a `<asset id>.json` report for every shading environment and mapping to `out_dir`
+pub fn export_all(index: &HD2Index, dictionary: &Dictionary, out_dir: impl AsRef<Path>) {
+    let out_dir = out_dir.as_ref();
+    let count = export_each(
+        index,
+        &[
+            DataType::shading_environment,
+            DataType::shading_environment_mapping,
+        ],
+        |id| {
+            let report = decode(index, dictionary, id)?;
+            write_json(out_dir.join(format!("{}.json", report.id)), &report);
+            Ok(())
+        },
+    );
+    println!("Exported {count} shading environments");
+}
@@ -0,0 +1,52 @@
+//! Float runs of `speedtree` assets.
+//!
+//! Lists the assets a tree references and where the runs of floats of its gpu part are. The vertex
+//! and index buffers of the LODs aren't parsed, so no geometry is exported.
+
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::decode::scan::{find_float_runs, find_refs, AssetRef};
+use crate::decode::{export_each, write_json};
+use crate::hash::Dictionary;
+use crate::index::HD2Index;
+use crate::parse::DataType;
+
+/// Runs shorter than that are more likely parameters than vertices
+const MIN_RUN_LEN: usize = 12;
+
+#[derive(Debug, Serialize)]
+pub struct SpeedTreeReport {
+    pub id: String,
+    pub name: Option<String>,
+    pub references: Vec<AssetRef>,
+    /// Offset and float count of the float runs of the gpu part
+    pub runs: Vec<(usize, usize)>,
+}
+
+pub fn decode(index: &HD2Index, dictionary: &Dictionary, id: u64) -> io::Result<SpeedTreeReport> {
+    let data = index.load_data_bytes(id)?;
+    let gpu = index.load_gpu_bytes(id).unwrap_or_default();
+    Ok(SpeedTreeReport {
+        id: format!("{id:016x}"),
+        name: dictionary.get(id).map(str::to_owned),
+        references: find_refs(&data, index, dictionary),
+        runs: find_float_runs(&gpu, MIN_RUN_LEN)
+            .iter()
+            .map(|run| (run.offset, run.values.len()))
+            .collect(),
+    })
+}
+
+/// Export a `<asset id>.json` report for every SpeedTree to `out_dir`
+pub fn export_all(index: &HD2Index, dictionary: &Dictionary, out_dir: impl AsRef<Path>) {
+    let out_dir = out_dir.as_ref();
+    let count = export_each(index, &[DataType::speedtree], |id| {
+        let report = decode(index, dictionary, id)?;
+        write_json(out_dir.join(format!("{}.json", report.id)), &report);
+        Ok(())
+    });
+    println!("Exported {count} SpeedTrees");
+}
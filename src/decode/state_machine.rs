@@ -0,0 +1,51 @@
+//! References of `state_machine` assets (animation controllers).
+//!
+//! Lists the animations and skeleton a controller uses, and the bone and event names found by thin
+//! hash. States, transitions, blend trees and variables aren't decoded.
+
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::decode::scan::{find_refs, find_thin_names, refs_of_type, AssetRef, ThinName};
+use crate::decode::{export_each, write_json};
+use crate::hash::Dictionary;
+use crate::index::HD2Index;
+use crate::parse::DataType;
+
+#[derive(Debug, Serialize)]
+pub struct StateMachineRefs {
+    pub id: String,
+    pub name: Option<String>,
+    pub animations: Vec<AssetRef>,
+    pub bones: Vec<AssetRef>,
+    /// Known names by thin hash, bone and animation event names
+    pub thin_names: Vec<ThinName>,
+    /// Every resolved reference, including the animations and bones
+    pub references: Vec<AssetRef>,
+}
+
+pub fn decode(index: &HD2Index, dictionary: &Dictionary, id: u64) -> io::Result<StateMachineRefs> {
+    let data = index.load_data_bytes(id)?;
+    let references = find_refs(&data, index, dictionary);
+    Ok(StateMachineRefs {
+        id: format!("{id:016x}"),
+        name: dictionary.get(id).map(str::to_owned),
+        animations: refs_of_type(&references, DataType::animation),
+        bones: refs_of_type(&references, DataType::bones),
+        thin_names: find_thin_names(&data, dictionary, &references),
+        references,
+    })
+}
+
+/// Export a `<asset id>.json` reference report for every state machine to `out_dir`
+pub fn export_all(index: &HD2Index, dictionary: &Dictionary, out_dir: impl AsRef<Path>) {
+    let out_dir = out_dir.as_ref();
+    let count = export_each(index, &[DataType::state_machine], |id| {
+        let report = decode(index, dictionary, id)?;
+        write_json(out_dir.join(format!("{}.json", report.id)), &report);
+        Ok(())
+    });
+    println!("Exported {count} state machines");
+}
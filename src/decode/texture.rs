@@ -0,0 +1,550 @@
+//! `texture` assets.
+//!
+//! The data part is a 0xC0 bytes engine header followed by a DDS header. Mips are stored after the
+//! DDS header or, for the streamed textures, in the stream or gpu part. We only decode the top mip,
+//! which is enough to export images.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::decode::u32_at;
+use crate::index::HD2Index;
+
+const DDS_OFFSET: usize = 0xC0;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc5,
+    Bc7,
+    Rgba8,
+    Bgra8,
+}
+
+impl Format {
+    fn from_fourcc(fourcc: &[u8]) -> Option<Self> {
+        Some(match fourcc {
+            b"DXT1" => Format::Bc1,
+            b"DXT2" | b"DXT3" => Format::Bc2,
+            b"DXT4" | b"DXT5" => Format::Bc3,
+            b"ATI1" | b"BC4U" => Format::Bc4,
+            b"ATI2" | b"BC5U" => Format::Bc5,
+            _ => return None,
+        })
+    }
+
+    fn from_dxgi(format: u32) -> Option<Self> {
+        Some(match format {
+            70..=72 => Format::Bc1,
+            73..=75 => Format::Bc2,
+            76..=78 => Format::Bc3,
+            79..=81 => Format::Bc4,
+            82..=84 => Format::Bc5,
+            97..=99 => Format::Bc7,
+            27..=29 => Format::Rgba8,
+            87 | 90 | 91 => Format::Bgra8,
+            _ => return None,
+        })
+    }
+
+    /// Bytes per 4x4 block, or per pixel for uncompressed formats
+    fn block_size(self) -> usize {
+        match self {
+            Format::Bc1 | Format::Bc4 => 8,
+            Format::Bc2 | Format::Bc3 | Format::Bc5 | Format::Bc7 => 16,
+            Format::Rgba8 | Format::Bgra8 => 4,
+        }
+    }
+
+    fn is_compressed(self) -> bool {
+        !matches!(self, Format::Rgba8 | Format::Bgra8)
+    }
+
+    /// Size of a mip in bytes, `None` if it doesn't fit in memory
+    fn mip_size(self, width: u32, height: u32) -> Option<usize> {
+        let (w, h) = if self.is_compressed() {
+            (width.div_ceil(4).max(1), height.div_ceil(4).max(1))
+        } else {
+            (width, height)
+        };
+        (w as usize)
+            .checked_mul(h as usize)?
+            .checked_mul(self.block_size())
+    }
+}
+
+#[derive(Debug)]
+pub struct TextureHeader {
+    pub width: u32,
+    pub height: u32,
+    pub mip_count: u32,
+    pub format: Option<Format>,
+    /// Offset of the pixel data in the data part
+    pub data_offset: usize,
+}
+
+impl TextureHeader {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        Self::parse_dds(data, DDS_OFFSET)
+    }
+
+    /// Parse a DDS header that starts at `offset` in `data`
+    pub fn parse_dds(data: &[u8], offset: usize) -> Option<Self> {
+        let dds = data.get(offset..)?;
+        if !dds.starts_with(b"DDS ") {
+            return None;
+        }
+        let fourcc = dds.get(84..88)?;
+        let (format, header_size) = if fourcc == b"DX10" {
+            (Format::from_dxgi(u32_at(dds, 128)?), 148)
+        } else if u32_at(dds, 80)? & 0x4 != 0 {
+            (Format::from_fourcc(fourcc), 128)
+        } else {
+            // Uncompressed, tell RGBA and BGRA apart with the red mask
+            let format = match (u32_at(dds, 88)?, u32_at(dds, 92)?) {
+                (32, 0x000000FF) => Some(Format::Rgba8),
+                (32, 0x00FF0000) => Some(Format::Bgra8),
+                _ => None,
+            };
+            (format, 128)
+        };
+        Some(Self {
+            height: u32_at(dds, 12)?,
+            width: u32_at(dds, 16)?,
+            mip_count: u32_at(dds, 28)?.max(1),
+            format,
+            data_offset: offset + header_size,
+        })
+    }
+}
+
+/// RGBA8 pixels
+#[derive(Debug)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Option<Image> {
+        let fits =
+            |start: u32, len: u32, max: u32| start.checked_add(len).is_some_and(|e| e <= max);
+        if !fits(x, width, self.width) || !fits(y, height, self.height) || width == 0 || height == 0
+        {
+            return None;
+        }
+        let (x, row_size, stride) = (x as usize, width as usize * 4, self.width as usize);
+        let mut pixels = Vec::with_capacity(row_size * height as usize);
+        for row in y as usize..(y + height) as usize {
+            let start = (row * stride + x) * 4;
+            pixels.extend_from_slice(&self.pixels[start..start + row_size]);
+        }
+        Some(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn write_png(&self, path: impl AsRef<Path>) {
+        let w = BufWriter::new(File::create(path).unwrap());
+        let mut encoder = png::Encoder::new(w, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&self.pixels).unwrap();
+    }
+}
+
+fn rgb565(c: u16) -> [u8; 4] {
+    let r = ((c >> 11) & 0x1F) as u32;
+    let g = ((c >> 5) & 0x3F) as u32;
+    let b = (c & 0x1F) as u32;
+    [
+        (r * 255 / 31) as u8,
+        (g * 255 / 63) as u8,
+        (b * 255 / 31) as u8,
+        255,
+    ]
+}
+
+fn lerp(a: u8, b: u8, wa: u32, wb: u32) -> u8 {
+    ((a as u32 * wa + b as u32 * wb) / (wa + wb)) as u8
+}
+
+/// 16 RGBA colors of a BC1 color block. BC2/BC3 blocks always use the 4 colors mode.
+fn decode_color_block(block: &[u8], always_opaque: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (p0, p1) = (rgb565(c0), rgb565(c1));
+    let mut palette = [p0, p1, [0; 4], [0; 4]];
+    for i in 0..3 {
+        if c0 > c1 || always_opaque {
+            palette[2][i] = lerp(p0[i], p1[i], 2, 1);
+            palette[3][i] = lerp(p0[i], p1[i], 1, 2);
+        } else {
+            palette[2][i] = lerp(p0[i], p1[i], 1, 1);
+        }
+    }
+    palette[2][3] = 255;
+    palette[3][3] = if c0 > c1 || always_opaque { 255 } else { 0 };
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+    std::array::from_fn(|i| palette[((indices >> (i * 2)) & 3) as usize])
+}
+
+/// 16 values of a BC3 alpha / BC4 block
+fn decode_alpha_block(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0], block[1]);
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for i in 0..6 {
+            palette[i + 2] = lerp(a0, a1, 6 - i as u32, 1 + i as u32);
+        }
+    } else {
+        for i in 0..4 {
+            palette[i + 2] = lerp(a0, a1, 4 - i as u32, 1 + i as u32);
+        }
+    }
+    let mut bits = 0u64;
+    for (i, &b) in block[2..8].iter().enumerate() {
+        bits |= (b as u64) << (i * 8);
+    }
+    std::array::from_fn(|i| palette[((bits >> (i * 3)) & 7) as usize])
+}
+
+/// BC7 modes: subsets, partition bits, rotation bits, index selection bits, color bits, alpha
+/// bits, endpoint p-bits, shared p-bits, index bits, secondary index bits
+const BC7_MODES: [[u32; 10]; 8] = [
+    [3, 4, 0, 0, 4, 0, 1, 0, 3, 0],
+    [2, 6, 0, 0, 6, 0, 0, 1, 3, 0],
+    [3, 6, 0, 0, 5, 0, 0, 0, 2, 0],
+    [2, 6, 0, 0, 7, 0, 1, 0, 2, 0],
+    [1, 0, 2, 1, 5, 6, 0, 0, 2, 3],
+    [1, 0, 2, 0, 7, 8, 0, 0, 2, 2],
+    [1, 0, 0, 0, 7, 7, 1, 0, 4, 0],
+    [2, 6, 0, 0, 5, 5, 1, 0, 2, 0],
+];
+
+/// 2 subsets partitions, bit `i` is the subset of pixel `i`
+const BC7_PARTITIONS2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800,
+    0xFFE8, 0xFF00, 0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC,
+    0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718,
+    0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// 3 subsets partitions, bits `2i..2i+2` are the subset of pixel `i`
+const BC7_PARTITIONS3: [u32; 64] = [
+    0xAA685050, 0x6A5A5040, 0x5A5A4200, 0x5450A0A8, 0xA5A50000, 0xA0A05050, 0x5555A0A0, 0x5A5A5050,
+    0xAA550000, 0xAA555500, 0xAAAA5500, 0x90909090, 0x94949494, 0xA4A4A4A4, 0xA9A59450, 0x2A0A4250,
+    0xA5945040, 0x0A425054, 0xA5A5A500, 0x55A0A0A0, 0xA8A85454, 0x6A6A4040, 0xA4A45000, 0x1A1A0500,
+    0x0050A4A4, 0xAAA59090, 0x14696914, 0x69691400, 0xA08585A0, 0xAA821414, 0x50A4A450, 0x6A5A0200,
+    0xA9A58000, 0x5090A0A8, 0xA8A09050, 0x24242424, 0x00AA5500, 0x24924924, 0x24499224, 0x50A50A50,
+    0x500AA550, 0xAAAA4444, 0x66660000, 0xA5A0A5A0, 0x50A050A0, 0x69286928, 0x44AAAA44, 0x66666600,
+    0xAA444444, 0x54A854A8, 0x95809580, 0x96969600, 0xA85454A8, 0x80959580, 0xAA141414, 0x96960000,
+    0xAAAA1414, 0xA05050A0, 0xA0A5A5A0, 0x96000000, 0x40804080, 0xA9A8A9A8, 0xAAAAAA44, 0x2A4A5254,
+];
+
+/// Anchor pixel of the second subset of the 2 subsets partitions
+const BC7_ANCHORS2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor pixel of the second subset of the 3 subsets partitions
+const BC7_ANCHORS3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+
+/// Anchor pixel of the third subset of the 3 subsets partitions
+const BC7_ANCHORS3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+const BC7_WEIGHTS2: [u32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Reads a block from its least significant bit
+struct Bits(u128, u32);
+
+impl Bits {
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.0 >> self.1) as u32 & ((1u64 << count) - 1) as u32;
+        self.1 += count;
+        value
+    }
+}
+
+/// Subset of each pixel and the anchor pixel of each subset
+fn bc7_partition(subsets: u32, partition: usize) -> ([usize; 16], [usize; 3]) {
+    match subsets {
+        2 => (
+            std::array::from_fn(|i| (BC7_PARTITIONS2[partition] >> i) as usize & 1),
+            [0, BC7_ANCHORS2[partition] as usize, 0],
+        ),
+        3 => (
+            std::array::from_fn(|i| (BC7_PARTITIONS3[partition] >> (i * 2)) as usize & 3),
+            [
+                0,
+                BC7_ANCHORS3_SECOND[partition] as usize,
+                BC7_ANCHORS3_THIRD[partition] as usize,
+            ],
+        ),
+        _ => ([0; 16], [0; 3]),
+    }
+}
+
+/// 16 indices of `bits` bits, anchors are stored with one bit less
+fn bc7_indices(r: &mut Bits, bits: u32, subset: &[usize; 16], anchors: &[usize; 3]) -> [u32; 16] {
+    std::array::from_fn(|i| {
+        let anchor = anchors[subset[i]] == i;
+        r.read(bits - anchor as u32)
+    })
+}
+
+fn bc7_weight(bits: u32, index: u32) -> u32 {
+    match bits {
+        2 => BC7_WEIGHTS2[index as usize],
+        3 => BC7_WEIGHTS3[index as usize],
+        _ => BC7_WEIGHTS4[index as usize],
+    }
+}
+
+fn decode_bc7_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut r = Bits(u128::from_le_bytes(block.try_into().unwrap()), 0);
+    let Some(mode) = (0..8).find(|_| r.read(1) == 1) else {
+        // Reserved
+        return [[0; 4]; 16];
+    };
+    let [ns, pb, rb, isb, cb, ab, epb, spb, ib, ib2] = BC7_MODES[mode];
+    let partition = r.read(pb) as usize;
+    let rotation = r.read(rb);
+    let index_selection = r.read(isb);
+
+    let endpoints = ns as usize * 2;
+    let mut colors = [[0u32; 4]; 6];
+    for c in 0..3 {
+        for e in colors.iter_mut().take(endpoints) {
+            e[c] = r.read(cb);
+        }
+    }
+    for e in colors.iter_mut().take(endpoints) {
+        e[3] = if ab > 0 { r.read(ab) } else { 255 };
+    }
+    let pbits: Vec<u32> = if epb > 0 {
+        (0..endpoints).map(|_| r.read(1)).collect()
+    } else if spb > 0 {
+        (0..ns).flat_map(|_| [r.read(1); 2]).collect()
+    } else {
+        Vec::new()
+    };
+    // Unquantize to 8 bits, the p-bit is the least significant bit when there is one
+    for (i, e) in colors.iter_mut().take(endpoints).enumerate() {
+        for (c, v) in e.iter_mut().enumerate() {
+            let bits = if c < 3 { cb } else { ab };
+            if bits == 0 {
+                continue;
+            }
+            let (value, precision) = match pbits.get(i) {
+                Some(p) => (*v << 1 | p, bits + 1),
+                None => (*v, bits),
+            };
+            let value = value << (8 - precision);
+            *v = value | value >> precision;
+        }
+    }
+
+    let (subset, anchors) = bc7_partition(ns, partition);
+    let indices = bc7_indices(&mut r, ib, &subset, &anchors);
+    let secondary = (ib2 > 0).then(|| bc7_indices(&mut r, ib2, &[0; 16], &[0; 3]));
+    std::array::from_fn(|i| {
+        let (e0, e1) = (colors[subset[i] * 2], colors[subset[i] * 2 + 1]);
+        let (mut color_index, mut alpha_index) = ((ib, indices[i]), (ib, indices[i]));
+        if let Some(secondary) = &secondary {
+            alpha_index = (ib2, secondary[i]);
+            if index_selection == 1 {
+                (color_index, alpha_index) = (alpha_index, color_index);
+            }
+        }
+        let mut pixel: [u8; 4] = std::array::from_fn(|c| {
+            let (bits, index) = if c < 3 { color_index } else { alpha_index };
+            let w = bc7_weight(bits, index);
+            ((e0[c] * (64 - w) + e1[c] * w + 32) >> 6) as u8
+        });
+        if rotation > 0 {
+            pixel.swap(rotation as usize - 1, 3);
+        }
+        pixel
+    })
+}
+
+fn decode_block(format: Format, block: &[u8]) -> [[u8; 4]; 16] {
+    match format {
+        Format::Bc1 => decode_color_block(block, false),
+        Format::Bc2 => {
+            let mut colors = decode_color_block(&block[8..], true);
+            for (i, c) in colors.iter_mut().enumerate() {
+                let a = (block[i / 2] >> ((i % 2) * 4)) & 0xF;
+                c[3] = a * 17;
+            }
+            colors
+        }
+        Format::Bc3 => {
+            let mut colors = decode_color_block(&block[8..], true);
+            for (c, a) in colors.iter_mut().zip(decode_alpha_block(block)) {
+                c[3] = a;
+            }
+            colors
+        }
+        Format::Bc4 => decode_alpha_block(block).map(|v| [v, v, v, 255]),
+        Format::Bc5 => {
+            let r = decode_alpha_block(block);
+            let g = decode_alpha_block(&block[8..]);
+            std::array::from_fn(|i| [r[i], g[i], 0, 255])
+        }
+        Format::Bc7 => decode_bc7_block(block),
+        Format::Rgba8 | Format::Bgra8 => unreachable!(),
+    }
+}
+
+/// Decode the top mip of `bytes` to RGBA8. Returns `None` if `bytes` is too short for the
+/// dimensions.
+pub fn decode_mip(format: Format, width: u32, height: u32, bytes: &[u8]) -> Option<Image> {
+    let size = format.mip_size(width, height)?;
+    let bytes = bytes.get(..size)?;
+    let (w, h) = (width as usize, height as usize);
+    let mut pixels = vec![0; w.checked_mul(h)?.checked_mul(4)?];
+    if !format.is_compressed() {
+        pixels.copy_from_slice(bytes);
+        if format == Format::Bgra8 {
+            pixels.chunks_exact_mut(4).for_each(|p| p.swap(0, 2));
+        }
+    } else {
+        let blocks_w = w.div_ceil(4).max(1);
+        for (i, block) in bytes.chunks_exact(format.block_size()).enumerate() {
+            let (bx, by) = (i % blocks_w * 4, i / blocks_w * 4);
+            for (j, color) in decode_block(format, block).iter().enumerate() {
+                let (x, y) = (bx + j % 4, by + j / 4);
+                if x < w && y < h {
+                    let p = (y * w + x) * 4;
+                    pixels[p..p + 4].copy_from_slice(color);
+                }
+            }
+        }
+    }
+    Some(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Load and decode the top mip of a texture. Returns `None` for unsupported formats.
+pub fn load_image(index: &HD2Index, id: u64) -> Option<Image> {
+    let data = index.load_data_bytes(id).ok()?;
+    let header = TextureHeader::parse(&data)?;
+    let format = header.format?;
+    let size = format.mip_size(header.width, header.height)?;
+    // The top mip is in the first part that is large enough to hold it
+    let candidates = [
+        Some(data[header.data_offset.min(data.len())..].to_vec()),
+        index.load_stream_bytes(id).ok(),
+        index.load_gpu_bytes(id).ok(),
+    ];
+    let bytes = candidates.into_iter().flatten().find(|b| b.len() >= size)?;
+    decode_mip(format, header.width, header.height, &bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decode::texture::{bc7_partition, decode_bc7_block, decode_mip, Format};
+
+    #[test]
+    fn bc1_block() {
+        // Red and blue endpoints, first row uses the 4 palette entries
+        let block = [0x00, 0xF8, 0x1F, 0x00, 0b11_10_01_00, 0, 0, 0];
+        let image = decode_mip(Format::Bc1, 4, 4, &block).unwrap();
+        assert_eq!(&image.pixels[0..4], &[255, 0, 0, 255]);
+        assert_eq!(&image.pixels[4..8], &[0, 0, 255, 255]);
+        assert_eq!(&image.pixels[8..12], &[170, 0, 85, 255]);
+        assert_eq!(&image.pixels[12..16], &[85, 0, 170, 255]);
+        // Rest of the block uses index 0
+        assert_eq!(&image.pixels[60..64], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc3_alpha_block() {
+        let mut block = [0u8; 16];
+        // Alpha endpoints 255 and 0, all indices 1
+        block[0] = 255;
+        let indices: u64 = (0..16).fold(0, |acc, i| acc | 1 << (i * 3));
+        block[2..8].copy_from_slice(&indices.to_le_bytes()[..6]);
+        let image = decode_mip(Format::Bc3, 4, 4, &block).unwrap();
+        assert!(image.pixels.chunks_exact(4).all(|p| p[3] == 0));
+    }
+
+    #[test]
+    fn bc7_anchors_are_in_their_subset() {
+        for subsets in [2, 3] {
+            for partition in 0..64 {
+                let (subset, anchors) = bc7_partition(subsets, partition);
+                for (s, &anchor) in anchors.iter().enumerate().take(subsets as usize) {
+                    assert_eq!(subset[anchor], s, "partition {partition} of {subsets}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bc7_mode6_block() {
+        // Mode, R0 R1 G0 G1 B0 B1 A0 A1, p-bits, then the indices of the first 3 pixels
+        let fields = [
+            (1 << 6, 7),
+            (50, 7),
+            (127, 7),
+            (100, 7),
+            (0, 7),
+            (0, 7),
+            (64, 7),
+            (127, 7),
+            (0, 7),
+            (1, 1),
+            (0, 1),
+            (0, 3),
+            (15, 4),
+            (8, 4),
+        ];
+        let (mut bits, mut pos) = (0u128, 0);
+        for (value, len) in fields {
+            bits |= value << pos;
+            pos += len;
+        }
+        let pixels = decode_bc7_block(&bits.to_le_bytes());
+        assert_eq!(pixels[0], [101, 201, 1, 255]);
+        assert_eq!(pixels[1], [254, 0, 128, 0]);
+        assert_eq!(pixels[2], [182, 94, 68, 120]);
+        assert_eq!(pixels[15], [101, 201, 1, 255]);
+        // Reserved mode
+        assert_eq!(decode_bc7_block(&[0; 16]), [[0; 4]; 16]);
+    }
+
+    #[test]
+    fn oversized_mips() {
+        assert!(decode_mip(Format::Rgba8, u32::MAX, u32::MAX, &[0; 16]).is_none());
+        assert!(decode_mip(Format::Bc7, u32::MAX, u32::MAX, &[0; 16]).is_none());
+        assert!(decode_mip(Format::Bc1, 1 << 16, 1 << 16, &[0; 16]).is_none());
+    }
+}
@@ -0,0 +1,140 @@
+//! `texture_atlas` assets.
+//!
+//! Regions are keyed by hashed names, 64-bit or thin. For every known name we read the rectangle
+//! stored next to it, either as normalized uvs (min u, min v, max u, max v) or in pixels (x, y,
+//! width, height), and slice it from the `texture` the atlas references.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::decode::scan::{find_refs, find_thin_names, refs_of_type};
+use crate::decode::texture::{load_image, Image};
+use crate::decode::{export_each, f32_at, serialize_hex, u32_at, write_json};
+use crate::hash::Dictionary;
+use crate::index::HD2Index;
+use crate::parse::DataType;
+
+#[derive(Debug, Serialize)]
+pub struct Region {
+    #[serde(serialize_with = "serialize_hex")]
+    pub key: u64,
+    /// The key is a thin hash, its 32 bits
+    pub thin: bool,
+    pub name: String,
+    /// Offset of the key in the payload
+    pub offset: usize,
+    pub uv: bool,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TextureAtlasReport {
+    pub id: String,
+    pub name: Option<String>,
+    pub texture: Option<String>,
+    pub texture_size: Option<(u32, u32)>,
+    pub regions: Vec<Region>,
+}
+
+/// Read a rectangle in pixels at `offset`, trying normalized uvs first
+fn read_rect(bytes: &[u8], offset: usize, width: u32, height: u32) -> Option<(bool, [u32; 4])> {
+    let f: [f32; 4] = std::array::from_fn(|i| f32_at(bytes, offset + i * 4).unwrap_or(f32::NAN));
+    if f.iter().all(|v| (0.0..=1.0).contains(v)) && f[0] < f[2] && f[1] < f[3] {
+        let x = (f[0] * width as f32).round() as u32;
+        let y = (f[1] * height as f32).round() as u32;
+        let x2 = (f[2] * width as f32).round() as u32;
+        let y2 = (f[3] * height as f32).round() as u32;
+        return (x2 > x && y2 > y).then_some((true, [x, y, x2 - x, y2 - y]));
+    }
+    let u: [u32; 4] = std::array::from_fn(|i| u32_at(bytes, offset + i * 4).unwrap_or(0));
+    let fits = |start: u32, len: u32, max: u32| len > 0 && start.checked_add(len) <= Some(max);
+    (fits(u[0], u[2], width) && fits(u[1], u[3], height)).then_some((false, u))
+}
+
+/// Returns the report and the decoded backing texture
+pub fn decode(
+    index: &HD2Index,
+    dictionary: &Dictionary,
+    id: u64,
+) -> io::Result<(TextureAtlasReport, Option<Image>)> {
+    let data = index.load_data_bytes(id)?;
+    let refs = find_refs(&data, index, dictionary);
+    let texture = refs_of_type(&refs, DataType::texture).first().map(|r| r.id);
+    let image = texture.and_then(|t| load_image(index, t));
+
+    let mut regions = Vec::new();
+    if let Some(image) = &image {
+        // Offset, key, whether it is thin, size and name
+        let mut keys: Vec<_> = refs
+            .iter()
+            .filter(|r| r.type_id.is_none())
+            .filter_map(|r| Some((r.offset, r.id, false, 8, r.name.clone()?)))
+            .collect();
+        keys.extend(
+            find_thin_names(&data, dictionary, &refs)
+                .into_iter()
+                .map(|t| (t.offset, t.hash as u64, true, 4, t.name)),
+        );
+        keys.sort_by_key(|k| k.0);
+        for (offset, key, thin, key_size, name) in keys {
+            // Rectangle right after the key, or right before it
+            let rect =
+                read_rect(&data, offset + key_size, image.width, image.height).or_else(|| {
+                    let before = offset.checked_sub(16)?;
+                    read_rect(&data, before, image.width, image.height)
+                });
+            if let Some((uv, [x, y, width, height])) = rect {
+                regions.push(Region {
+                    key,
+                    thin,
+                    name,
+                    offset,
+                    uv,
+                    x,
+                    y,
+                    width,
+                    height,
+                });
+            }
+        }
+    }
+
+    let report = TextureAtlasReport {
+        id: format!("{id:016x}"),
+        name: dictionary.get(id).map(str::to_owned),
+        texture: texture.map(|t| format!("{t:016x}")),
+        texture_size: image.as_ref().map(|i| (i.width, i.height)),
+        regions,
+    };
+    Ok((report, image))
+}
+
+/// Export every region of every atlas to `out_dir/<atlas id>/<region name>.png`, with a
+/// `regions.json` report
+pub fn export_all(index: &HD2Index, dictionary: &Dictionary, out_dir: impl AsRef<Path>) {
+    let out_dir = out_dir.as_ref();
+    let mut sliced = 0;
+    let count = export_each(index, &[DataType::texture_atlas], |id| {
+        let (report, image) = decode(index, dictionary, id)?;
+        let dir = out_dir.join(&report.id);
+        fs::create_dir_all(&dir).unwrap();
+        if let Some(image) = image {
+            for region in &report.regions {
+                if let Some(slice) = image.crop(region.x, region.y, region.width, region.height) {
+                    let file_name = region.name.replace(['/', '\\', ':'], "_");
+                    slice.write_png(dir.join(format!("{file_name}.png")));
+                    sliced += 1;
+                }
+            }
+        }
+        write_json(dir.join("regions.json"), &report);
+        Ok(())
+    });
+    println!("Sliced {sliced} regions from {count} atlases");
+}
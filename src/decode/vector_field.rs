@@ -0,0 +1,124 @@
+//! `vector_field` assets.
+//!
+//! A field is a dense grid of vectors. The grid is found by looking for 3 dimensions early in the
+//! payload whose product matches the number of floats that follow them, with possibly a few more
+//! header fields (bounds...) in between.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use crate::decode::{export_each, f32_at, u32_at, write_json};
+use crate::hash::Dictionary;
+use crate::index::{HD2Index, Part};
+use crate::parse::DataType;
+
+/// How far in the payload the dimensions can be
+const MAX_HEADER_OFFSET: usize = 256;
+/// How many bytes can separate the dimensions from the grid
+const MAX_HEADER_GAP: usize = 64;
+
+#[derive(Debug, Serialize)]
+pub struct Grid {
+    pub part: Part,
+    pub dims_offset: usize,
+    pub data_offset: usize,
+    pub dims: [u32; 3],
+    /// Floats per cell
+    pub components: u32,
+    pub min: Vec<f32>,
+    pub max: Vec<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VectorFieldReport {
+    pub id: String,
+    pub name: Option<String>,
+    pub grid: Option<Grid>,
+}
+
+fn find_grid(part: Part, bytes: &[u8]) -> Option<Grid> {
+    for dims_offset in (0..MAX_HEADER_OFFSET.min(bytes.len())).step_by(4) {
+        let (Some(x), Some(y), Some(z)) = (
+            u32_at(bytes, dims_offset),
+            u32_at(bytes, dims_offset + 4),
+            u32_at(bytes, dims_offset + 8),
+        ) else {
+            break;
+        };
+        if ![x, y, z].iter().all(|d| (1..=1024).contains(d)) {
+            continue;
+        }
+        let cells = x as usize * y as usize * z as usize;
+        for data_offset in (dims_offset + 12..=dims_offset + 12 + MAX_HEADER_GAP).step_by(4) {
+            let Some(remaining) = bytes.len().checked_sub(data_offset) else {
+                break;
+            };
+            for components in [4, 3, 2] {
+                if cells * components * 4 != remaining {
+                    continue;
+                }
+                let mut min = vec![f32::MAX; components];
+                let mut max = vec![f32::MIN; components];
+                for cell in 0..cells {
+                    for c in 0..components {
+                        let f = f32_at(bytes, data_offset + (cell * components + c) * 4)?;
+                        min[c] = min[c].min(f);
+                        max[c] = max[c].max(f);
+                    }
+                }
+                return Some(Grid {
+                    part,
+                    dims_offset,
+                    data_offset,
+                    dims: [x, y, z],
+                    components: components as u32,
+                    min,
+                    max,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Returns the report and the grid floats
+pub fn decode(index: &HD2Index, dictionary: &Dictionary, id: u64) -> (VectorFieldReport, Vec<u8>) {
+    let mut found = None;
+    for part in Part::iter() {
+        let Ok(bytes) = index.load_part_bytes(id, part) else {
+            continue;
+        };
+        if let Some(grid) = find_grid(part, &bytes) {
+            let raw = bytes[grid.data_offset..].to_vec();
+            found = Some((grid, raw));
+            break;
+        }
+    }
+    let (grid, raw) = found.unzip();
+    let report = VectorFieldReport {
+        id: format!("{id:016x}"),
+        name: dictionary.get(id).map(str::to_owned),
+        grid,
+    };
+    (report, raw.unwrap_or_default())
+}
+
+/// Export a `<asset id>.json` metadata file for every vector field to `out_dir`, along with the
+/// grid as a `<asset id>.raw` little endian f32 volume, in payload order, when it was found.
+pub fn export_all(index: &HD2Index, dictionary: &Dictionary, out_dir: impl AsRef<Path>) {
+    let out_dir = out_dir.as_ref();
+    let mut found = 0;
+    let count = export_each(index, &[DataType::vector_field], |id| {
+        let (report, raw) = decode(index, dictionary, id);
+        write_json(out_dir.join(format!("{}.json", report.id)), &report);
+        if report.grid.is_some() {
+            fs::write(out_dir.join(format!("{}.raw", report.id)), raw).unwrap();
+            found += 1;
+        }
+        Ok(())
+    });
+    println!("Exported {count} vector fields, found the grid of {found}");
+}
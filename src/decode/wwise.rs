@@ -0,0 +1,414 @@
+//! `wwise_bank`, `wwise_stream`, `wwise_dep` and `wwise_metadata` assets.
+//!
+//! Banks are regular Wwise soundbanks behind a small engine header. Their HIRC section lists the
+//! sounds and the media they play, either embedded in the bank (DIDX/DATA) or streamed. Streamed
+//! media are `wwise_stream` assets named `content/audio/<source id>`. A `wwise_dep` holds the name
+//! of a bank, `wwise_metadata` isn't reversed yet and we only report its strings and references.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::decode::scan::{find_refs, find_strings, AssetRef, FoundString};
+use crate::decode::{serialize_hex, u32_at, write_json};
+use crate::hash::{stingray_hash, Dictionary};
+use crate::index::HD2Index;
+use crate::parse::DataType;
+
+/// HIRC object type of sounds
+const HIRC_SOUND: u8 = 2;
+
+/// Read from streamed media to find their duration, enough for the RIFF chunks before `data`
+const WEM_HEADER_SIZE: usize = 4096;
+
+/// Name of the `wwise_stream` asset holding a streamed media
+pub fn stream_name(source_id: u32) -> String {
+    format!("content/audio/{source_id}")
+}
+
+/// Iterate over the (tag, payload offset, payload size) of the bank sections
+fn sections(bank: &[u8]) -> impl Iterator<Item = ([u8; 4], usize, usize)> + '_ {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        let tag = bank.get(pos..pos + 4)?.try_into().ok()?;
+        let size = u32_at(bank, pos + 4)? as usize;
+        let section = (tag, pos + 8, size);
+        pos += 8 + size;
+        Some(section)
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct Sound {
+    pub sound_id: u32,
+    pub source_id: u32,
+    /// 0 embedded, 1 prefetched, 2 streamed
+    pub stream_type: u8,
+    /// The `wwise_stream` asset if the media is streamed and in the index
+    #[serde(serialize_with = "serialize_opt_hex")]
+    pub stream: Option<u64>,
+    /// Approximate duration in seconds, from the media header
+    pub duration: Option<f32>,
+}
+
+fn serialize_opt_hex<S: serde::Serializer>(id: &Option<u64>, s: S) -> Result<S::Ok, S::Error> {
+    match id {
+        Some(id) => serialize_hex(id, s),
+        None => s.serialize_none(),
+    }
+}
+
+/// Duration of a WEM (RIFF) media, data size over the average byte rate of the format
+pub fn wem_duration(wem: &[u8]) -> Option<f32> {
+    if !wem.starts_with(b"RIFF") {
+        return None;
+    }
+    let mut byte_rate = None;
+    let mut pos = 12;
+    while pos + 8 <= wem.len() {
+        let tag = &wem[pos..pos + 4];
+        let size = u32_at(wem, pos + 4)? as usize;
+        match tag {
+            b"fmt " => byte_rate = u32_at(wem, pos + 16).filter(|&r| r > 0),
+            b"data" => return Some(size as f32 / byte_rate? as f32),
+            _ => {}
+        }
+        // Chunks are word aligned
+        pos += 8 + size + (size & 1);
+    }
+    None
+}
+
+#[derive(Debug, Serialize)]
+pub struct BankReport {
+    pub id: String,
+    pub name: Option<String>,
+    pub version: Option<u32>,
+    pub sounds: Vec<Sound>,
+    /// Ids of the `wwise_stream` assets the bank depends on
+    pub streams: Vec<String>,
+    /// `wwise_metadata` assets that mention this bank
+    pub metadata: Vec<String>,
+}
+
+fn parse_bank(index: &HD2Index, data: &[u8]) -> (Option<u32>, Vec<Sound>) {
+    let Some(start) = data.windows(4).position(|w| w == b"BKHD") else {
+        return (None, Vec::new());
+    };
+    let bank = &data[start..];
+    let mut version = None;
+    // Durations of the embedded media
+    let mut embedded = HashMap::new();
+    let mut didx = Vec::new();
+    let mut sounds = Vec::new();
+    for (tag, offset, size) in sections(bank) {
+        let Some(section) = bank.get(offset..offset + size) else {
+            break;
+        };
+        match &tag {
+            b"BKHD" => version = u32_at(section, 0),
+            b"DIDX" => {
+                for entry in section.chunks_exact(12) {
+                    didx.push((
+                        u32_at(entry, 0).unwrap(),
+                        u32_at(entry, 4).unwrap() as usize,
+                        u32_at(entry, 8).unwrap() as usize,
+                    ));
+                }
+            }
+            b"DATA" => {
+                for &(media, offset, size) in &didx {
+                    if let Some(wem) = section.get(offset..offset + size) {
+                        embedded.insert(media, wem_duration(wem));
+                    }
+                }
+            }
+            b"HIRC" => {
+                let count = u32_at(section, 0).unwrap_or(0);
+                let mut pos = 4;
+                for _ in 0..count {
+                    let (Some(&ty), Some(obj_size)) = (section.get(pos), u32_at(section, pos + 1))
+                    else {
+                        break;
+                    };
+                    let obj = pos + 5;
+                    if ty == HIRC_SOUND {
+                        // id, plugin id, stream type, source id
+                        if let (Some(sound_id), Some(&stream_type), Some(source_id)) = (
+                            u32_at(section, obj),
+                            section.get(obj + 8),
+                            u32_at(section, obj + 9),
+                        ) {
+                            sounds.push(Sound {
+                                sound_id,
+                                source_id,
+                                stream_type,
+                                stream: None,
+                                duration: None,
+                            });
+                        }
+                    }
+                    pos = obj + obj_size as usize;
+                }
+            }
+            _ => {}
+        }
+    }
+    for sound in &mut sounds {
+        let stream = stingray_hash(stream_name(sound.source_id).as_bytes());
+        if index
+            .get(stream)
+            .is_some_and(|e| e.record.type_id == DataType::wwise_stream)
+        {
+            sound.stream = Some(stream);
+            sound.duration = index
+                .load_stream_prefix(stream, WEM_HEADER_SIZE)
+                .ok()
+                .and_then(|wem| wem_duration(&wem));
+        } else if let Some(&duration) = embedded.get(&sound.source_id) {
+            sound.duration = duration;
+        }
+    }
+    (version, sounds)
+}
+
+#[derive(Debug, Serialize)]
+pub struct DepReport {
+    pub id: String,
+    /// Name of the bank, the first path-like string in the payload
+    pub bank_name: Option<String>,
+    #[serde(serialize_with = "serialize_hex")]
+    pub bank: u64,
+}
+
+pub fn decode_dep(index: &HD2Index, id: u64) -> io::Result<DepReport> {
+    let data = index.load_data_bytes(id)?;
+    let bank_name = find_strings(&data, 4)
+        .into_iter()
+        .map(|s| s.value)
+        .find(|s| s.contains('/'));
+    Ok(DepReport {
+        id: format!("{id:016x}"),
+        bank: bank_name
+            .as_ref()
+            .map(|n| stingray_hash(n.as_bytes()))
+            .unwrap_or(id),
+        bank_name,
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetadataReport {
+    pub id: String,
+    pub name: Option<String>,
+    pub strings: Vec<FoundString>,
+    pub references: Vec<AssetRef>,
+}
+
+pub fn decode_metadata(
+    index: &HD2Index,
+    dictionary: &Dictionary,
+    id: u64,
+) -> io::Result<MetadataReport> {
+    let data = index.load_data_bytes(id)?;
+    Ok(MetadataReport {
+        id: format!("{id:016x}"),
+        name: dictionary.get(id).map(str::to_owned),
+        strings: find_strings(&data, 4),
+        references: find_refs(&data, index, dictionary),
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct AudioReport {
+    pub banks: Vec<BankReport>,
+    pub deps: Vec<DepReport>,
+    pub metadata: Vec<MetadataReport>,
+}
+
+/// `decode` every asset of type `ty`, the ones that can't be read are reported and skipped
+fn decode_each<T>(
+    index: &HD2Index,
+    ty: DataType,
+    mut decode: impl FnMut(u64) -> io::Result<T>,
+) -> Vec<T> {
+    index
+        .ids_of_type(ty)
+        .filter_map(|id| {
+            decode(id)
+                .map_err(|e| eprintln!("Skipping {id:016x}: {e}"))
+                .ok()
+        })
+        .collect()
+}
+
+/// Decode every bank, dep and metadata and link them together
+pub fn decode_all(index: &HD2Index, dictionary: &Dictionary) -> AudioReport {
+    let deps = decode_each(index, DataType::wwise_dep, |id| decode_dep(index, id));
+    let metadata = decode_each(index, DataType::wwise_metadata, |id| {
+        decode_metadata(index, dictionary, id)
+    });
+
+    let mut banks = Vec::new();
+    for id in index.ids_of_type(DataType::wwise_bank) {
+        let data = match index.load_data_bytes(id) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Skipping {id:016x}: {e}");
+                continue;
+            }
+        };
+        let (version, sounds) = parse_bank(index, &data);
+        let name = dictionary.get(id).map(str::to_owned).or_else(|| {
+            deps.iter()
+                .find(|d| d.bank == id)
+                .and_then(|d| d.bank_name.clone())
+        });
+        let mut streams: Vec<_> = sounds
+            .iter()
+            .filter_map(|s| s.stream)
+            .map(|s| format!("{s:016x}"))
+            .collect();
+        streams.sort();
+        streams.dedup();
+        let hex_id = format!("{id:016x}");
+        let linked_metadata = metadata
+            .iter()
+            .filter(|m| m.id == hex_id || m.references.iter().any(|r| r.id == id))
+            .map(|m| m.id.clone())
+            .collect();
+        banks.push(BankReport {
+            id: hex_id,
+            name,
+            version,
+            sounds,
+            streams,
+            metadata: linked_metadata,
+        });
+    }
+    banks.sort_by(|a, b| a.id.cmp(&b.id));
+    AudioReport {
+        banks,
+        deps,
+        metadata,
+    }
+}
+
+/// Print one line per sound: source id, name, bank and duration
+pub fn print_sounds(report: &AudioReport, dictionary: &Dictionary) {
+    println!(
+        "{:>10}  {:<48}  {:<48}  {:>8}",
+        "source", "name", "bank", "duration"
+    );
+    for bank in &report.banks {
+        let bank_name = bank.name.as_deref().unwrap_or(&bank.id);
+        for sound in &bank.sounds {
+            let name = sound
+                .stream
+                .and_then(|s| dictionary.get(s))
+                .map(str::to_owned)
+                .unwrap_or_else(|| stream_name(sound.source_id));
+            let duration = sound
+                .duration
+                .map(|d| format!("{d:.2}s"))
+                .unwrap_or_else(|| "?".to_owned());
+            println!(
+                "{:>10}  {name:<48}  {bank_name:<48}  {duration:>8}",
+                sound.source_id
+            );
+        }
+    }
+}
+
+/// Export the linked banks, deps and metadata to `out_dir/audio.json`
+pub fn export_all(index: &HD2Index, dictionary: &Dictionary, out_dir: impl AsRef<Path>) {
+    let report = decode_all(index, dictionary);
+    write_json(out_dir.as_ref().join("audio.json"), &report);
+    println!(
+        "Exported {} banks, {} deps and {} metadata",
+        report.banks.len(),
+        report.deps.len(),
+        report.metadata.len()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use speedy::Readable;
+
+    use crate::decode::wwise::{parse_bank, wem_duration};
+    use crate::index::HD2Index;
+
+    fn chunk(tag: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut chunk = tag.to_vec();
+        chunk.extend((payload.len() as u32).to_le_bytes());
+        chunk.extend(payload);
+        chunk
+    }
+
+    /// A RIFF header with an odd sized chunk before `fmt `, the `data` chunk has no samples
+    fn wem(byte_rate: u32, data_size: u32) -> Vec<u8> {
+        let mut fmt = vec![0xFE, 0xFF, 2, 0];
+        fmt.extend(48000u32.to_le_bytes());
+        fmt.extend(byte_rate.to_le_bytes());
+        fmt.extend([4, 0, 16, 0]);
+        let mut wem = b"RIFF\0\0\0\0WAVE".to_vec();
+        wem.extend(chunk(b"JUNK", &[1, 2, 3]));
+        wem.push(0);
+        wem.extend(chunk(b"fmt ", &fmt));
+        wem.extend(b"data");
+        wem.extend(data_size.to_le_bytes());
+        wem
+    }
+
+    #[test]
+    fn wem_durations() {
+        assert_eq!(wem_duration(&wem(4000, 8000)), Some(2.0));
+        assert_eq!(wem_duration(&wem(0, 8000)), None);
+        assert_eq!(wem_duration(&wem(4000, 8000)[4..]), None);
+        // `data` before `fmt `
+        let mut wem = b"RIFF\0\0\0\0WAVE".to_vec();
+        wem.extend(chunk(b"data", &[]));
+        assert_eq!(wem_duration(&wem), None);
+    }
+
+    #[test]
+    fn bank_sounds() {
+        let media = wem(1000, 500);
+        let mut didx = 7u32.to_le_bytes().to_vec();
+        didx.extend(0u32.to_le_bytes());
+        didx.extend((media.len() as u32).to_le_bytes());
+        // An event, then sounds with an embedded and a streamed source
+        let mut hirc = 3u32.to_le_bytes().to_vec();
+        hirc.extend([4, 2, 0, 0, 0, 0xAA, 0xBB]);
+        for (sound_id, stream_type, source_id) in [(100u32, 0u8, 7u32), (101, 2, 9)] {
+            let mut sound = sound_id.to_le_bytes().to_vec();
+            sound.extend(0x00040001u32.to_le_bytes());
+            sound.push(stream_type);
+            sound.extend(source_id.to_le_bytes());
+            hirc.push(2);
+            hirc.extend((sound.len() as u32).to_le_bytes());
+            hirc.extend(sound);
+        }
+        let mut data = vec![0; 16];
+        data.extend(chunk(b"BKHD", &[150, 0, 0, 0, 1, 2, 3, 4]));
+        data.extend(chunk(b"DIDX", &didx));
+        data.extend(chunk(b"DATA", &media));
+        data.extend(chunk(b"HIRC", &hirc));
+
+        // No base dir, no assets
+        let index = HD2Index::read_from_buffer(&[0; 8]).unwrap();
+        let (version, sounds) = parse_bank(&index, &data);
+        assert_eq!(version, Some(150));
+        let sounds: Vec<_> = sounds
+            .iter()
+            .map(|s| (s.sound_id, s.source_id, s.stream_type, s.stream, s.duration))
+            .collect();
+        assert_eq!(
+            sounds,
+            [(100, 7, 0, None, Some(0.5)), (101, 9, 2, None, None)]
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::fmt::Write;
 use std::fs;
 use std::hash::{BuildHasher, Hasher};
 use std::path::Path;
@@ -66,6 +67,124 @@ pub fn stingray_hash(key: &[u8]) -> u64 {
     h
 }
 
+/// The upper half of [`stingray_hash`], used for bone names, material parameters and string keys
+pub fn stingray_thin_hash(key: &[u8]) -> u32 {
+    (stingray_hash(key) >> 32) as u32
+}
+
+const M: u64 = 0xc6a4a7935bd1e995;
+const R: u32 = 47;
+
+/// How many keys [`stingray_hash_batch`] hashes in lockstep
+const LANES: usize = 8;
+
+#[inline(always)]
+fn mix_block(h: u64, block: &[u8]) -> u64 {
+    let mut k = u64::from_le_bytes(block.try_into().unwrap());
+    k = k.wrapping_mul(M);
+    k ^= k >> R;
+    k = k.wrapping_mul(M);
+    (h ^ k).wrapping_mul(M)
+}
+
+#[inline(always)]
+fn mix_tail(mut h: u64, tail: &[u8]) -> u64 {
+    if !tail.is_empty() {
+        let mut k = [0; 8];
+        k[..tail.len()].copy_from_slice(tail);
+        h ^= u64::from_le_bytes(k);
+        h = h.wrapping_mul(M);
+    }
+    h
+}
+
+#[inline(always)]
+fn finalize(mut h: u64) -> u64 {
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^ (h >> R)
+}
+
+/// [`stingray_hash`] of every key into `out`.
+///
+/// Keys are processed [`LANES`] at a time, block after block, so that the independent
+/// multiplications of the lanes can overlap. Whether that beats hashing the keys one after the
+/// other depends on the target and the key lengths, compare with `cargo bench --bench hash`.
+pub fn stingray_hash_batch(keys: &[&[u8]], out: &mut [u64]) {
+    assert_eq!(keys.len(), out.len());
+    let mut chunks = keys.chunks_exact(LANES);
+    let mut outs = out.chunks_exact_mut(LANES);
+    for (keys, out) in chunks.by_ref().zip(outs.by_ref()) {
+        let keys: &[&[u8]; LANES] = keys.try_into().unwrap();
+        let mut h: [u64; LANES] = std::array::from_fn(|l| (keys[l].len() as u64).wrapping_mul(M));
+        // Blocks all the lanes have
+        let common = keys.iter().map(|k| k.len() / 8).min().unwrap();
+        for i in 0..common {
+            for l in 0..LANES {
+                h[l] = mix_block(h[l], &keys[l][i * 8..i * 8 + 8]);
+            }
+        }
+        for l in 0..LANES {
+            let mut blocks = keys[l][common * 8..].chunks_exact(8);
+            let h = blocks.by_ref().fold(h[l], mix_block);
+            out[l] = finalize(mix_tail(h, blocks.remainder()));
+        }
+    }
+    for (key, out) in chunks.remainder().iter().zip(outs.into_remainder()) {
+        *out = stingray_hash(key);
+    }
+}
+
+/// [`stingray_hash`] of a key fed in pieces. murmur64a starts from the key length, so it has to be
+/// known upfront.
+#[derive(Debug, Clone)]
+pub struct StingrayHasher {
+    h: u64,
+    len: u64,
+    fed: u64,
+    /// Bytes of the current incomplete block
+    pending: [u8; 8],
+    pending_len: usize,
+}
+
+impl StingrayHasher {
+    pub fn new(len: u64) -> Self {
+        Self {
+            h: len.wrapping_mul(M),
+            len,
+            fed: 0,
+            pending: [0; 8],
+            pending_len: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        self.fed += bytes.len() as u64;
+        assert!(self.fed <= self.len, "more bytes than the announced length");
+        if self.pending_len > 0 {
+            let n = bytes.len().min(8 - self.pending_len);
+            self.pending[self.pending_len..self.pending_len + n].copy_from_slice(&bytes[..n]);
+            self.pending_len += n;
+            bytes = &bytes[n..];
+            if self.pending_len < 8 {
+                return;
+            }
+            self.h = mix_block(self.h, &self.pending);
+            self.pending_len = 0;
+        }
+        let mut blocks = bytes.chunks_exact(8);
+        self.h = blocks.by_ref().fold(self.h, mix_block);
+        let rest = blocks.remainder();
+        self.pending[..rest.len()].copy_from_slice(rest);
+        self.pending_len = rest.len();
+    }
+
+    pub fn finish(&self) -> u64 {
+        assert_eq!(self.fed, self.len, "fewer bytes than the announced length");
+        finalize(mix_tail(self.h, &self.pending[..self.pending_len]))
+    }
+}
+
 #[derive(Default)]
 pub struct NoHash;
 pub struct NoHashHasher(u64);
@@ -82,6 +201,11 @@ impl Hasher for NoHashHasher {
     fn write_u64(&mut self, i: u64) {
         self.0 = i;
     }
+
+    fn write_u32(&mut self, i: u32) {
+        // The table looks at both the low and the high bits
+        self.0 = (i as u64) << 32 | i as u64;
+    }
 }
 
 impl BuildHasher for NoHash {
@@ -92,29 +216,306 @@ impl BuildHasher for NoHash {
     }
 }
 
-#[derive(Debug)]
+/// Where names come from. Higher priorities win collisions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub name: String,
+    pub priority: i32,
+}
+
+#[derive(Debug, Clone)]
+struct Name {
+    name: String,
+    /// Index in `Dictionary::sources`
+    source: usize,
+}
+
+/// Two different names with the same hash
+#[derive(Debug, Clone)]
+pub struct Collision {
+    pub hash: u64,
+    /// Only the thin hashes collide
+    pub thin: bool,
+    pub kept: String,
+    pub dropped: String,
+    pub dropped_source: String,
+}
+
+/// Comment line giving the source of the names that follow it
+pub const SOURCE_COMMENT: &str = "// source: ";
+
+/// Split `<name> (priority <n>)` from a source comment
+fn parse_source(comment: &str) -> Option<(&str, i32)> {
+    let (name, priority) = comment.strip_suffix(')')?.rsplit_once(" (priority ")?;
+    Some((name, priority.parse().ok()?))
+}
+
+/// Names by hash, with where they come from
+#[derive(Debug, Default)]
 pub struct Dictionary {
-    map: HashMap<u64, String, NoHash>,
+    map: HashMap<u64, Name, NoHash>,
+    /// The same names by thin hash
+    thin: HashMap<u32, Name, NoHash>,
+    sources: Vec<Source>,
+    collisions: Vec<Collision>,
+}
+
+/// Whether `a` should replace `b`: higher priority first, then the smallest name and source so
+/// that the result doesn't depend on the loading order
+fn wins(sources: &[Source], a: &Name, b: &Name) -> bool {
+    let (sa, sb) = (&sources[a.source], &sources[b.source]);
+    (sb.priority, &a.name, &sa.name) < (sa.priority, &b.name, &sb.name)
 }
 
 impl Dictionary {
+    /// A dictionary of the names in a single file
     pub fn load(path: impl AsRef<Path>) -> Self {
-        let mut map = HashMap::with_hasher(NoHash);
+        let mut dictionary = Self::default();
+        dictionary.add_file(path, 0);
+        dictionary
+    }
+
+    fn source(&mut self, name: &str, priority: i32) -> usize {
+        match self.sources.iter().position(|s| s.name == name) {
+            Some(i) => i,
+            None => {
+                self.sources.push(Source {
+                    name: name.to_owned(),
+                    priority,
+                });
+                self.sources.len() - 1
+            }
+        }
+    }
+
+    /// Add the names of a text file. Names following a `// source: <name> (priority <n>)` line
+    /// are attributed to that source, the other ones to the file itself with `priority`, as well as
+    /// sources without a priority.
+    pub fn add_file(&mut self, path: impl AsRef<Path>, priority: i32) {
+        let path = path.as_ref();
+        let mut source = self.source(&path.display().to_string(), priority);
         for line in fs::read_to_string(path).unwrap().lines() {
+            if let Some(comment) = line.strip_prefix(SOURCE_COMMENT) {
+                let (name, source_priority) = parse_source(comment).unwrap_or((comment, priority));
+                source = self.source(name, source_priority);
+                continue;
+            }
             if line.is_empty() || line.starts_with("//") {
                 continue;
             }
-            let hash = stingray_hash(line.as_bytes());
-            map.insert(hash, line.to_string());
+            self.insert(line.to_owned(), source);
+        }
+    }
+
+    /// Add names from another source, harvested strings or `hash_lookup` assets
+    pub fn add_names<S: Into<String>>(
+        &mut self,
+        source: &str,
+        priority: i32,
+        names: impl IntoIterator<Item = S>,
+    ) {
+        let source = self.source(source, priority);
+        for name in names {
+            self.insert(name.into(), source);
+        }
+    }
+
+    /// Add all the names of `other`, with their sources
+    pub fn merge(&mut self, other: &Dictionary) {
+        let mut names: Vec<_> = other.map.values().collect();
+        names.sort_by(|a, b| a.name.cmp(&b.name));
+        for name in names {
+            let source = &other.sources[name.source];
+            let source = self.source(&source.name, source.priority);
+            self.insert(name.name.clone(), source);
+        }
+    }
+
+    fn insert(&mut self, name: String, source: usize) {
+        let hash = stingray_hash(name.as_bytes());
+        let new = Name { name, source };
+        match self.map.get(&hash) {
+            None => {
+                self.map.insert(hash, new.clone());
+            }
+            Some(old) => {
+                let new_wins = wins(&self.sources, &new, old);
+                if old.name != new.name {
+                    let (kept, dropped) = if new_wins { (&new, old) } else { (old, &new) };
+                    self.collisions.push(Collision {
+                        hash,
+                        thin: false,
+                        kept: kept.name.clone(),
+                        dropped: dropped.name.clone(),
+                        dropped_source: self.sources[dropped.source].name.clone(),
+                    });
+                }
+                if new_wins {
+                    self.map.insert(hash, new.clone());
+                }
+            }
+        }
+
+        let thin_hash = (hash >> 32) as u32;
+        match self.thin.get(&thin_hash) {
+            None => {
+                self.thin.insert(thin_hash, new);
+            }
+            Some(old) => {
+                let new_wins = wins(&self.sources, &new, old);
+                // Full collisions are already reported
+                if stingray_hash(old.name.as_bytes()) != hash {
+                    let (kept, dropped) = if new_wins { (&new, old) } else { (old, &new) };
+                    self.collisions.push(Collision {
+                        hash: thin_hash as u64,
+                        thin: true,
+                        kept: kept.name.clone(),
+                        dropped: dropped.name.clone(),
+                        dropped_source: self.sources[dropped.source].name.clone(),
+                    });
+                }
+                if new_wins {
+                    self.thin.insert(thin_hash, new);
+                }
+            }
         }
-        Self { map }
     }
 
     pub fn get(&self, hash: u64) -> Option<&str> {
-        self.map.get(&hash).map(|s| s.as_str())
+        self.map.get(&hash).map(|n| n.name.as_str())
+    }
+
+    pub fn get_thin(&self, hash: u32) -> Option<&str> {
+        self.thin.get(&hash).map(|n| n.name.as_str())
+    }
+
+    /// Where the name of `hash` comes from
+    pub fn source_of(&self, hash: u64) -> Option<&Source> {
+        self.map.get(&hash).map(|n| &self.sources[n.source])
+    }
+
+    pub fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+
+    /// Collisions met while loading, in loading order
+    pub fn collisions(&self) -> &[Collision] {
+        &self.collisions
     }
 
     pub fn len(&self) -> usize {
         self.map.len()
     }
+
+    /// Identifies the set of hashes with a name, whatever the names and the loading order
+    pub fn fingerprint(&self) -> u64 {
+        self.map
+            .keys()
+            .fold(self.map.len() as u64, |acc, &hash| acc.wrapping_add(hash))
+    }
+
+    /// Every thin hash with the name that won it, in no particular order
+    pub fn thin_entries(&self) -> impl Iterator<Item = (u32, &str)> {
+        self.thin.iter().map(|(&hash, n)| (hash, n.name.as_str()))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.map.values().map(|n| n.name.as_str())
+    }
+
+    /// Every hash with its name and the index of its source in [`Self::sources`], in no particular
+    /// order
+    pub fn entries(&self) -> impl Iterator<Item = (u64, &str, usize)> {
+        self.map
+            .iter()
+            .map(|(&hash, n)| (hash, n.name.as_str(), n.source))
+    }
+
+    /// Save the names grouped by source, by decreasing priority, each group sorted. The output only
+    /// depends on the content, not on the loading order.
+    pub fn save(&self, path: impl AsRef<Path>) {
+        let mut names: Vec<_> = self.map.values().collect();
+        names.sort_by(|a, b| {
+            let (sa, sb) = (&self.sources[a.source], &self.sources[b.source]);
+            (sb.priority, &sa.name, &a.name).cmp(&(sa.priority, &sb.name, &b.name))
+        });
+        let mut out = String::new();
+        let mut source = None;
+        for name in names {
+            if source != Some(name.source) {
+                source = Some(name.source);
+                let Source { name, priority } = &self.sources[name.source];
+                writeln!(out, "{SOURCE_COMMENT}{name} (priority {priority})").unwrap();
+            }
+            out.push_str(&name.name);
+            out.push('\n');
+        }
+        fs::write(path, out).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn priority_wins_in_any_order() {
+        let mut a = Dictionary::default();
+        a.add_names("curated", 1, ["content/audio/music"]);
+        a.add_names("harvest", 0, ["content/audio/music"]);
+        let mut b = Dictionary::default();
+        b.add_names("harvest", 0, ["content/audio/music"]);
+        b.add_names("curated", 1, ["content/audio/music"]);
+        let hash = stingray_hash(b"content/audio/music");
+        assert_eq!(a.source_of(hash).unwrap().name, "curated");
+        assert_eq!(b.source_of(hash).unwrap().name, "curated");
+        assert!(a.collisions().is_empty());
+        assert_eq!(a.get_thin((hash >> 32) as u32), Some("content/audio/music"));
+    }
+
+    #[test]
+    fn save_keeps_priorities() {
+        let mut a = Dictionary::default();
+        a.add_names("curated", 2, ["content/audio/music"]);
+        a.add_names("harvest", -1, ["content/audio/sfx"]);
+        let path = std::env::temp_dir().join("hd2re_save_keeps_priorities.txt");
+        a.save(&path);
+        let mut b = Dictionary::default();
+        b.add_names("cracked", 1, ["content/audio/music"]);
+        b.add_file(&path, 0);
+        let hash = stingray_hash(b"content/audio/music");
+        assert_eq!(b.source_of(hash).unwrap().name, "curated");
+        assert_eq!(b.source_of(hash).unwrap().priority, 2);
+        let sfx = stingray_hash(b"content/audio/sfx");
+        assert_eq!(b.source_of(sfx).unwrap().priority, -1);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    proptest! {
+        #[test]
+        fn batch_matches_scalar(keys in vec(vec(any::<u8>(), 0..64), 0..40)) {
+            let keys: Vec<&[u8]> = keys.iter().map(Vec::as_slice).collect();
+            let mut out = vec![0; keys.len()];
+            stingray_hash_batch(&keys, &mut out);
+            for (key, hash) in keys.iter().zip(out) {
+                prop_assert_eq!(hash, stingray_hash(key));
+            }
+        }
+
+        #[test]
+        fn streaming_matches_scalar(key in vec(any::<u8>(), 0..256), cuts in vec(0..256usize, 0..8)) {
+            let mut cuts: Vec<_> = cuts.into_iter().map(|c| c.min(key.len())).collect();
+            cuts.sort();
+            let mut hasher = StingrayHasher::new(key.len() as u64);
+            let mut start = 0;
+            for cut in cuts.into_iter().chain([key.len()]) {
+                hasher.update(&key[start..cut]);
+                start = cut;
+            }
+            prop_assert_eq!(hasher.finish(), stingray_hash(&key));
+        }
+    }
 }
@@ -8,10 +8,12 @@ use std::{fs, io};
 
 use binrw::io::BufReader;
 use binrw::BinRead;
+use serde::Serialize;
 use speedy::{Readable, Writable};
+use strum::{Display, EnumIter};
 
 use crate::hash::NoHash;
-use crate::parse::{DataRecord, Hd2DataFile};
+use crate::parse::{DataRecord, DataType, Hd2DataFile};
 
 pub type AssetMap<V> = HashMap<u64, V, NoHash>;
 
@@ -28,6 +30,18 @@ pub struct Entry {
     pub record: DataRecord,
 }
 
+/// The 3 places an asset can store bytes in
+#[derive(
+    Debug, Readable, Writable, Serialize, Eq, PartialEq, Hash, EnumIter, Copy, Clone, Display,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Part {
+    Data,
+    Stream,
+    Gpu,
+}
+
 impl HD2Index {
     pub fn create_index(path: impl AsRef<Path>) -> Self {
         let path = path.as_ref();
@@ -81,7 +95,7 @@ impl HD2Index {
 
     pub fn load_n_data_bytes<const N: usize>(&self, id: u64) -> io::Result<[u8; N]> {
         let Entry { file_id, record } = &self.items[&id];
-        if record.stream_size < N as u32 {
+        if record.data_size < N as u32 {
             return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
         }
         let mut file = File::open(self.resolve_data_file(*file_id))?;
@@ -112,6 +126,16 @@ impl HD2Index {
         Ok(buf)
     }
 
+    /// The first `len` bytes of the stream part, fewer if it is smaller
+    pub fn load_stream_prefix(&self, id: u64, len: usize) -> io::Result<Vec<u8>> {
+        let Entry { file_id, record } = &self.items[&id];
+        let mut file = File::open(self.resolve_stream_file(*file_id))?;
+        let mut buf = vec![0; len.min(record.stream_size as usize)];
+        file.seek(SeekFrom::Start(record.stream_offset as u64))?;
+        file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
     pub fn load_gpu_bytes(&self, id: u64) -> io::Result<Vec<u8>> {
         let Entry { file_id, record } = &self.items[&id];
         let mut file = File::open(self.resolve_gpu_file(*file_id))?;
@@ -133,9 +157,33 @@ impl HD2Index {
         Ok(buf)
     }
 
+    pub fn load_part_bytes(&self, id: u64, part: Part) -> io::Result<Vec<u8>> {
+        match part {
+            Part::Data => self.load_data_bytes(id),
+            Part::Stream => self.load_stream_bytes(id),
+            Part::Gpu => self.load_gpu_bytes(id),
+        }
+    }
+
     pub fn ids(&self) -> impl Iterator<Item = u64> + '_ {
         self.items.keys().copied()
     }
+
+    /// Ids of all the assets of type `ty`
+    pub fn ids_of_type(&self, ty: DataType) -> impl Iterator<Item = u64> + '_ {
+        self.items
+            .iter()
+            .filter(move |(_, e)| e.record.type_id == ty)
+            .map(|(&id, _)| id)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Entry> {
+        self.items.get(&id)
+    }
+
+    pub fn contains(&self, id: u64) -> bool {
+        self.items.contains_key(&id)
+    }
 }
 
 impl Index<u64> for HD2Index {
@@ -145,3 +193,32 @@ impl Index<u64> for HD2Index {
         &self.items[&index]
     }
 }
+
+#[cfg(test)]
+impl HD2Index {
+    /// An index of assets that only have a data part, given as archive, id, type, offset and size
+    pub(crate) fn from_data_parts(
+        base_dir: &str,
+        parts: &[(u64, u64, DataType, u64, u32)],
+    ) -> Self {
+        let mut items = AssetMap::with_hasher(NoHash);
+        for (index, &(file_id, id, type_id, offset, data_size)) in parts.iter().enumerate() {
+            let record = DataRecord {
+                id,
+                type_id,
+                offset,
+                stream_offset: 0,
+                gpu_offset: 0,
+                data_size,
+                stream_size: 0,
+                gpu_size: 0,
+                index: index as u32,
+            };
+            items.insert(id, Entry { file_id, record });
+        }
+        Self {
+            base_dir: base_dir.to_owned(),
+            items,
+        }
+    }
+}
@@ -1,5 +1,8 @@
+pub mod analysis;
 pub mod convert;
+pub mod decode;
 pub mod hash;
 pub mod index;
+pub mod names;
 pub mod parse;
 pub mod sniff;
@@ -1,17 +1,224 @@
+use std::env;
+
 use speedy::{Readable, Writable};
 use strum::IntoEnumIterator;
 
+use hd2re::analysis::xref::{self, RefIndex};
+use hd2re::analysis::{consistency, coverage, workbench};
+use hd2re::decode::{
+    entity, flow, havok, mouse_cursor, particles, scene, shader, shading_environment, speedtree,
+    state_machine, texture_atlas, vector_field, write_json, wwise,
+};
 use hd2re::hash::{stingray_hash, Dictionary};
-use hd2re::index::HD2Index;
+use hd2re::index::{HD2Index, Part};
+use hd2re::names::compiled::{self, CompiledDictionary};
+use hd2re::names::crack::{self, Cracker};
+use hd2re::names::harvest;
 use hd2re::parse::DataType;
-use hd2re::sniff::libmagic::LibMagicSniff;
-use hd2re::sniff::magika::MagikaSniff;
+use hd2re::sniff::libmagic::{self, LibMagicConfig, LibMagicSniffer};
+use hd2re::sniff::magika::MagikaSniffer;
+use hd2re::sniff::signature::SignatureSniffer;
+use hd2re::sniff::Registry;
 
 fn main() {
-    // println!("{:x}", stringray_hash(b"packages/pre_boot"));
-    // println!("{:x}", stringray_hash(b"packages/boot"));
-    // println!("{:x}", stringray_hash(b"texture"));
-    println!("{:x}", stingray_hash(b"hash_lookup"));
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["analyze", ty, out_dir] => match workbench::parse_type(ty) {
+            Some(ty) => workbench::export(&load_index(), ty, out_dir),
+            None => eprintln!("Unknown type {ty}"),
+        },
+        ["audio", "ls"] => {
+            let dictionary = load_dictionary();
+            wwise::print_sounds(&wwise::decode_all(&load_index(), &dictionary), &dictionary)
+        }
+        ["audio", out_dir] => wwise::export_all(&load_index(), &load_dictionary(), out_dir),
+        ["consistency", out @ ..] if out.len() <= 1 => {
+            let index = load_index();
+            let report = consistency::consistency(&index, &load_sniffers(&index));
+            consistency::print_report(&report);
+            if let [out] = out {
+                write_json(out, &report);
+            }
+        }
+        ["coverage", out @ ..] if out.len() <= 1 => {
+            let report = coverage::coverage(&load_index(), &load_dictionary());
+            coverage::print_report(&report);
+            if let [out] = out {
+                write_json(out, &report);
+            }
+        }
+        ["crack", templates, hits] => {
+            let templates = crack::load_templates(templates);
+            Cracker::new(&load_index(), &load_dictionary(), templates).run(hits);
+        }
+        ["cursors", out_dir] => {
+            mouse_cursor::export_all(&load_index(), &load_dictionary(), out_dir)
+        }
+        ["dictionary", "merge", out, files @ ..] if !files.is_empty() => {
+            merge_dictionaries(out, files)
+        }
+        ["entity-refs", out_dir] => entity::export_all(&load_index(), &load_dictionary(), out_dir),
+        ["flow-refs", out_dir] => flow::export_all(&load_index(), &load_dictionary(), out_dir),
+        ["harvest"] => {
+            let mut dictionary = load_dictionary();
+            let hits = harvest::harvest(&load_index(), &dictionary);
+            harvest::append_to_dictionary(&hits, "dictionary.txt");
+            if compiled::is_compiled("dictionary.bin") {
+                harvest::add_to_dictionary(&hits, &mut dictionary);
+                compiled::compile(&dictionary, "dictionary.bin");
+            }
+        }
+        ["havok", out_dir] => havok::export_all(&load_index(), &load_dictionary(), out_dir),
+        ["particles-refs", out_dir] => {
+            particles::export_all(&load_index(), &load_dictionary(), out_dir)
+        }
+        ["refs", target] => {
+            let index = load_index();
+            let dictionary = load_dictionary();
+            let refs = load_refs(&index, &dictionary);
+            xref::print_referrers(&refs, &index, &dictionary, xref::parse_id(target))
+        }
+        ["scenes", out_dir] => scene::export_all(&load_index(), &load_dictionary(), out_dir),
+        ["shaders", out_dir] => shader::extract_all(&load_index(), out_dir),
+        ["shading-environments", out_dir] => {
+            shading_environment::export_all(&load_index(), &load_dictionary(), out_dir)
+        }
+        ["sniff", target] => sniff_asset(&load_index(), xref::parse_id(target)),
+        ["speedtree-runs", out_dir] => {
+            speedtree::export_all(&load_index(), &load_dictionary(), out_dir)
+        }
+        ["state-machine-refs", out_dir] => {
+            state_machine::export_all(&load_index(), &load_dictionary(), out_dir)
+        }
+        ["texture-atlases", out_dir] => {
+            texture_atlas::export_all(&load_index(), &load_dictionary(), out_dir)
+        }
+        ["unhash", hashes @ ..] if !hashes.is_empty() => {
+            let dictionary = load_dictionary();
+            for hash in hashes {
+                print_unhashed(&dictionary, hash);
+            }
+        }
+        ["vector-fields", out_dir] => {
+            vector_field::export_all(&load_index(), &load_dictionary(), out_dir)
+        }
+        [] => explore(),
+        _ => print_usage(),
+    }
+}
+
+fn print_usage() {
+    eprintln!("Usage: hd2re [command]");
+    eprintln!("Without a command, runs the exploration routine.");
+    eprintln!();
+    eprintln!("Analysis:");
+    eprintln!("  analyze <type> <out dir>        Field guesses and an ImHex pattern for a type");
+    eprintln!();
+    eprintln!("Names:");
+    eprintln!("  crack <templates> <hits file>   Guess names from templates and known names");
+    eprintln!("  dictionary merge <out> <file[:priority]>...");
+    eprintln!("                                  Merge text or compiled dictionaries, to a");
+    eprintln!("                                  compiled one if <out> ends with .bin. The file");
+    eprintln!("                                  hash_lookup reads the hash_lookup assets");
+    eprintln!("  harvest                         Add names from asset strings to the dictionary");
+    eprintln!();
+    eprintln!("Listings:");
+    eprintln!("  audio ls                        Sounds with their name, bank and duration");
+    eprintln!("  consistency [json file]         Sniffed labels against the type of each part");
+    eprintln!("  coverage [json file]            Named assets per type and archive");
+    eprintln!("  refs <id|name>                  Assets referencing an asset or a name");
+    eprintln!("  sniff <id|name>                 Signatures found in each part of an asset");
+    eprintln!("  unhash <hash>...                Names of 64-bit or thin (8 hex digits) hashes");
+    eprintln!();
+    eprintln!("Exports:");
+    eprintln!("  audio <out dir>                 Wwise banks linked to their streams and deps");
+    eprintln!("  cursors <out dir>               Mouse cursors to PNG with their hotspot");
+    eprintln!("  entity-refs <out dir>           Named hashes and references of entities");
+    eprintln!("  flow-refs <out dir>             Symbols, references and strings of flows");
+    eprintln!("  havok <out dir>                 Havok containers and their object lists to JSON");
+    eprintln!("  particles-refs <out dir>        Assets and float runs of particle effects");
+    eprintln!("  scenes <out dir>                Level and prefab placements to JSON");
+    eprintln!("  shaders <out dir>               Shader blobs, reflection and permutation records");
+    eprintln!("  shading-environments <out dir>  Environment settings to JSON");
+    eprintln!("  speedtree-runs <out dir>        References and gpu float runs of SpeedTrees");
+    eprintln!("  state-machine-refs <out dir>    Animations and bones of controllers");
+    eprintln!("  texture-atlases <out dir>       Texture atlas regions to PNG");
+    eprintln!("  vector-fields <out dir>         Vector fields to raw f32 volumes");
+}
+
+/// Guesses of the built-in signature sniffer, part by part
+fn sniff_asset(index: &HD2Index, id: u64) {
+    let Some(entry) = index.get(id) else {
+        eprintln!("{id:016x} isn't in the index");
+        return;
+    };
+    println!("{id:016x} {}", entry.record.type_id);
+    let mut sniffers = Registry::default();
+    sniffers.register(SignatureSniffer);
+    for part in Part::iter() {
+        if let Ok(bytes) = index.load_part_bytes(id, part) {
+            for guess in sniffers.sniff(part, &bytes) {
+                println!("  {part:<6} {:<16} {:.2}", guess.label, guess.confidence);
+            }
+        }
+    }
+}
+
+/// Up to 8 hex digits is a thin hash
+fn print_unhashed(dictionary: &Dictionary, hash: &str) {
+    let digits = hash.trim_start_matches("0x");
+    let name = match u64::from_str_radix(digits, 16) {
+        Ok(h) if digits.len() <= 8 => dictionary.get_thin(h as u32),
+        Ok(h) => dictionary.get(h),
+        Err(_) => {
+            eprintln!("{hash} isn't an hexadecimal hash");
+            return;
+        }
+    };
+    println!("{hash} -> {}", name.unwrap_or("?"));
+}
+
+fn merge_dictionaries(out: &str, files: &[&str]) {
+    let mut dictionary = Dictionary::default();
+    for file in files {
+        let (path, priority) = match file.rsplit_once(':') {
+            Some((path, priority)) if priority.parse::<i32>().is_ok() => {
+                (path, priority.parse().unwrap())
+            }
+            _ => (*file, 0),
+        };
+        if path == "hash_lookup" {
+            let count = harvest::add_hash_lookups(&load_index(), &mut dictionary, priority);
+            println!("Read {count} hash_lookup assets");
+        } else if compiled::is_compiled(path) {
+            // Compiled sources keep their own priorities
+            dictionary.merge(&CompiledDictionary::open(path).unwrap().to_dictionary());
+        } else {
+            dictionary.add_file(path, priority);
+        }
+    }
+    for c in dictionary.collisions() {
+        let width = if c.thin { "thin" } else { "full" };
+        println!(
+            "{width} collision {:x}: kept {}, dropped {} from {}",
+            c.hash, c.kept, c.dropped, c.dropped_source
+        );
+    }
+    if out.ends_with(".bin") {
+        compiled::compile(&dictionary, out);
+    } else {
+        dictionary.save(out);
+    }
+    println!(
+        "Saved {} names from {} sources, {} collisions",
+        dictionary.len(),
+        dictionary.sources().len(),
+        dictionary.collisions().len()
+    );
+}
+
+fn load_index() -> HD2Index {
     let index = if let Ok(hd2fs) = HD2Index::read_from_file("hd2index.bin") {
         println!("Loading saved hd2index.");
         hd2fs
@@ -22,20 +229,75 @@ fn main() {
         hd2fs
     };
     println!("Loaded metadata for {} assets", index.len());
+    index
+}
 
+fn load_refs(index: &HD2Index, dictionary: &Dictionary) -> RefIndex {
+    let saved = RefIndex::read_from_file("hd2refs.bin").ok();
+    let refs = if let Some(refs) = saved.filter(|r| r.matches(dictionary)) {
+        println!("Loading saved reference index.");
+        refs
+    } else {
+        println!("No reference index for this dictionary available, building ...");
+        let refs = RefIndex::create_index(index, dictionary);
+        refs.write_to_file("hd2refs.bin").unwrap();
+        refs
+    };
+    println!("Loaded references to {} ids", refs.len());
+    refs
+}
+
+/// `dictionary.bin` when it is compiled, `dictionary.txt` otherwise. `harvest` adds its names to
+/// both.
+fn load_dictionary() -> Dictionary {
+    if compiled::is_compiled("dictionary.bin") {
+        let dictionary = CompiledDictionary::open("dictionary.bin")
+            .unwrap()
+            .to_dictionary();
+        println!("Loaded compiled dictionary. ({} entries)", dictionary.len());
+        return dictionary;
+    }
     let dictionary = Dictionary::load("dictionary.txt");
     println!("Loaded dictionary. ({} entries)", dictionary.len());
+    dictionary
+}
 
-    for id in index.ids() {
-        if let Some(name) = dictionary.get(id) {
-            // The hash comes from a file name so it most probably contains a '/'
-            if name.contains('/') {
-                println!("match: {} -> {}", id, name);
-            } else {
-                println!("false match: {} -> {}", id, name);
-            }
-        }
+/// libmagic setup from the environment: `HD2_MAGIC_DB` lists the databases to use instead of the
+/// system one, `HD2_MAGIC_OUTPUT` is `description`, `mime` or `extension`.
+fn libmagic_config() -> LibMagicConfig {
+    let mut config = LibMagicConfig::default();
+    if let Some(paths) = env::var_os("HD2_MAGIC_DB") {
+        config.databases = env::split_paths(&paths).collect();
     }
+    config.output = match env::var("HD2_MAGIC_OUTPUT").as_deref() {
+        Ok("mime") => libmagic::Output::Mime,
+        Ok("extension") => libmagic::Output::Extension,
+        _ => libmagic::Output::Description,
+    };
+    config
+}
+
+/// Every sniffer, run on the assets they haven't seen yet
+fn load_sniffers(index: &HD2Index) -> Registry {
+    let mut sniffers = Registry::default();
+    sniffers
+        .register(SignatureSniffer)
+        .register(MagikaSniffer::default())
+        .register(LibMagicSniffer::new(&libmagic_config()));
+    sniffers.run(index, ".");
+    sniffers
+}
+
+fn explore() {
+    // println!("{:x}", stringray_hash(b"packages/pre_boot"));
+    // println!("{:x}", stringray_hash(b"packages/boot"));
+    // println!("{:x}", stringray_hash(b"texture"));
+    println!("{:x}", stingray_hash(b"hash_lookup"));
+    let index = load_index();
+
+    let dictionary = load_dictionary();
+
+    coverage::print_report(&coverage::coverage(&index, &dictionary));
 
     for ty in DataType::iter() {
         if let Some(name) = dictionary.get(ty as u64) {
@@ -45,51 +307,6 @@ fn main() {
 
     dbg!(DataType::iter().count());
 
-    let magika_sniff = if let Ok(sniff) = MagikaSniff::read_from_file("hd2sniff.magika.bin") {
-        println!("Loading saved magika sniff.");
-        sniff
-    } else {
-        println!("No magika sniff available.");
-        let sniff = MagikaSniff::run(&index);
-        sniff.write_to_file("hd2sniff.magika.bin").unwrap();
-        sniff
-    };
-    // for (&key, [data, stream, gpu]) in magika_sniff.results.iter() {
-    //     if !index[key].record.type_id.is_known() {
-    //         println!(
-    //             "{key:016x} ({:?}): ({}, {}), ({}, {}), ({}, {})",
-    //             index[key].record.type_id,
-    //             data[0].0,
-    //             &magika_sniff.labels[data[0].1 as usize],
-    //             stream[0].0,
-    //             &magika_sniff.labels[stream[0].1 as usize],
-    //             gpu[0].0,
-    //             &magika_sniff.labels[gpu[0].1 as usize]
-    //         );
-    //     }
-    // }
-    let libmagic_sniff = if let Ok(sniff) = LibMagicSniff::read_from_file("hd2sniff.libmagic.bin") {
-        println!("Loading saved libmagic sniff.");
-        sniff
-    } else {
-        println!("No libmagic sniff available.");
-        let sniff = LibMagicSniff::run(&index);
-        sniff.write_to_file("hd2sniff.libmagic.bin").unwrap();
-        sniff
-    };
-    // for (&key, (data, stream, gpu)) in &libmagic_sniff.results {
-    //     if !index[key].record.type_id.is_known()
-    //         && !(LibMagicSniff::guess_is_worthless(data)
-    //             && LibMagicSniff::guess_is_worthless(stream)
-    //             && LibMagicSniff::guess_is_worthless(gpu))
-    //     {
-    //         println!(
-    //             "{key:016x} ({:?}): {data}, {stream}, {gpu}",
-    //             index[key].record.type_id
-    //         );
-    //     }
-    // }
-
     // Dump WAV
     // fs::create_dir_all("data/audio/wem").unwrap();
     // for key in index.ids() {
@@ -0,0 +1,285 @@
+//! Compiled dictionaries: a sorted hash table and a string pool, memory mapped.
+//!
+//! Opening one doesn't hash or parse anything, lookups are binary searches in the mapped file. The
+//! layout, all little endian:
+//!
+//! | section | content                                                                 |
+//! |---------|-------------------------------------------------------------------------|
+//! | header  | magic `HD2DICT\0`, version u32, entries u32, sources u32, thin u32, pool u64 |
+//! | entries | hash u64, pool offset u32, length u32, source u32, sorted by hash         |
+//! | thin    | thin hash u32, entry u32 of the priority winner, sorted by thin hash      |
+//! | sources | priority i32, pool offset u32, length u32                               |
+//! | pool    | names and source names, utf-8                                           |
+
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::decode::{u32_at, u64_at};
+use crate::hash::{stingray_hash, Dictionary, Source};
+
+const MAGIC: &[u8; 8] = b"HD2DICT\0";
+const VERSION: u32 = 2;
+const HEADER_SIZE: usize = 32;
+const ENTRY_SIZE: usize = 20;
+const THIN_SIZE: usize = 8;
+const SOURCE_SIZE: usize = 12;
+
+pub struct CompiledDictionary {
+    mmap: Mmap,
+    entries: usize,
+    thin: usize,
+    sources: usize,
+}
+
+/// Sizes and indices are stored on fewer bits than `usize`, refuse what doesn't fit
+fn narrow<T: TryFrom<usize>>(value: usize, what: &str) -> T {
+    T::try_from(value).unwrap_or_else(|_| panic!("{what} {value} doesn't fit the compiled format"))
+}
+
+/// Write `dictionary` in the compiled format
+pub fn compile(dictionary: &Dictionary, path: impl AsRef<Path>) {
+    let sources = dictionary.sources();
+    let mut entries: Vec<_> = dictionary.entries().collect();
+    entries.sort_by_key(|e| e.0);
+
+    let mut pool: Vec<u8> = Vec::new();
+    let mut table = Vec::with_capacity(entries.len() * ENTRY_SIZE);
+    for &(hash, name, source) in &entries {
+        table.extend(hash.to_le_bytes());
+        table.extend(narrow::<u32>(pool.len(), "pool offset").to_le_bytes());
+        table.extend(narrow::<u32>(name.len(), "name length").to_le_bytes());
+        table.extend(narrow::<u32>(source, "source index").to_le_bytes());
+        pool.extend(name.as_bytes());
+    }
+
+    // The winner of a thin hash also wins its full hash, so it has an entry
+    let mut thin: Vec<_> = dictionary
+        .thin_entries()
+        .map(|(thin, name)| {
+            let hash = stingray_hash(name.as_bytes());
+            let entry = entries.binary_search_by_key(&hash, |e| e.0).unwrap();
+            (thin, narrow::<u32>(entry, "entry"))
+        })
+        .collect();
+    thin.sort();
+
+    let mut out = Vec::new();
+    out.extend(MAGIC);
+    out.extend(VERSION.to_le_bytes());
+    out.extend(narrow::<u32>(entries.len(), "entry count").to_le_bytes());
+    out.extend(narrow::<u32>(sources.len(), "source count").to_le_bytes());
+    out.extend(narrow::<u32>(thin.len(), "thin count").to_le_bytes());
+    let mut source_table = Vec::with_capacity(sources.len() * SOURCE_SIZE);
+    for source in sources {
+        source_table.extend(source.priority.to_le_bytes());
+        source_table.extend(narrow::<u32>(pool.len(), "pool offset").to_le_bytes());
+        source_table.extend(narrow::<u32>(source.name.len(), "source name length").to_le_bytes());
+        pool.extend(source.name.as_bytes());
+    }
+    out.extend((pool.len() as u64).to_le_bytes());
+    out.extend(table);
+    for (hash, entry) in thin {
+        out.extend(hash.to_le_bytes());
+        out.extend(entry.to_le_bytes());
+    }
+    out.extend(source_table);
+    out.extend(pool);
+    fs::write(path, out).unwrap();
+}
+
+/// Whether the file at `path` is a compiled dictionary
+pub fn is_compiled(path: impl AsRef<Path>) -> bool {
+    let mut magic = [0; 8];
+    File::open(path)
+        .and_then(|mut f| io::Read::read_exact(&mut f, &mut magic))
+        .is_ok()
+        && &magic == MAGIC
+}
+
+impl CompiledDictionary {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the file isn't expected to change while we use it
+        let mmap = unsafe { Mmap::map(&file)? };
+        let invalid = || io::Error::from(io::ErrorKind::InvalidData);
+        if mmap.get(..8) != Some(MAGIC) || u32_at(&mmap, 8) != Some(VERSION) {
+            return Err(invalid());
+        }
+        let entries = u32_at(&mmap, 12).ok_or_else(invalid)? as usize;
+        let sources = u32_at(&mmap, 16).ok_or_else(invalid)? as usize;
+        let thin = u32_at(&mmap, 20).ok_or_else(invalid)? as usize;
+        let pool = u64_at(&mmap, 24).ok_or_else(invalid)? as usize;
+        let size =
+            HEADER_SIZE + entries * ENTRY_SIZE + thin * THIN_SIZE + sources * SOURCE_SIZE + pool;
+        if mmap.len() != size {
+            return Err(invalid());
+        }
+        Ok(Self {
+            mmap,
+            entries,
+            thin,
+            sources,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries == 0
+    }
+
+    fn thin_start(&self) -> usize {
+        HEADER_SIZE + self.entries * ENTRY_SIZE
+    }
+
+    fn sources_start(&self) -> usize {
+        self.thin_start() + self.thin * THIN_SIZE
+    }
+
+    fn pool_str(&self, offset: usize, len: usize) -> &str {
+        let start = self.sources_start() + self.sources * SOURCE_SIZE + offset;
+        std::str::from_utf8(&self.mmap[start..start + len]).unwrap()
+    }
+
+    fn entry_hash(&self, i: usize) -> u64 {
+        u64_at(&self.mmap, HEADER_SIZE + i * ENTRY_SIZE).unwrap()
+    }
+
+    /// Name and source index of the entry `i`
+    fn entry(&self, i: usize) -> (&str, usize) {
+        let at = HEADER_SIZE + i * ENTRY_SIZE;
+        let offset = u32_at(&self.mmap, at + 8).unwrap() as usize;
+        let len = u32_at(&self.mmap, at + 12).unwrap() as usize;
+        let source = u32_at(&self.mmap, at + 16).unwrap() as usize;
+        (self.pool_str(offset, len), source)
+    }
+
+    fn find(&self, hash: u64) -> Option<usize> {
+        let (mut lo, mut hi) = (0, self.entries);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            match self.entry_hash(mid).cmp(&hash) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
+
+    pub fn get(&self, hash: u64) -> Option<&str> {
+        self.find(hash).map(|i| self.entry(i).0)
+    }
+
+    /// The name that won `hash` among the names sharing it
+    pub fn get_thin(&self, hash: u32) -> Option<&str> {
+        let start = self.thin_start();
+        let thin_hash = |i: usize| u32_at(&self.mmap, start + i * THIN_SIZE).unwrap();
+        let (mut lo, mut hi) = (0, self.thin);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            match thin_hash(mid).cmp(&hash) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => {
+                    let entry = u32_at(&self.mmap, start + mid * THIN_SIZE + 4).unwrap();
+                    return Some(self.entry(entry as usize).0);
+                }
+            }
+        }
+        None
+    }
+
+    pub fn source(&self, i: usize) -> Source {
+        let at = self.sources_start() + i * SOURCE_SIZE;
+        Source {
+            priority: u32_at(&self.mmap, at).unwrap() as i32,
+            name: self
+                .pool_str(
+                    u32_at(&self.mmap, at + 4).unwrap() as usize,
+                    u32_at(&self.mmap, at + 8).unwrap() as usize,
+                )
+                .to_owned(),
+        }
+    }
+
+    pub fn source_of(&self, hash: u64) -> Option<Source> {
+        self.find(hash).map(|i| self.source(self.entry(i).1))
+    }
+
+    /// Load every entry back, to merge new names in or save as text
+    pub fn to_dictionary(&self) -> Dictionary {
+        let mut by_source = vec![Vec::new(); self.sources];
+        for i in 0..self.entries {
+            let (name, source) = self.entry(i);
+            by_source[source].push(name);
+        }
+        let mut dictionary = Dictionary::default();
+        for (i, names) in by_source.into_iter().enumerate() {
+            let source = self.source(i);
+            dictionary.add_names(&source.name, source.priority, names);
+        }
+        dictionary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{stingray_hash, stingray_thin_hash};
+
+    #[test]
+    fn roundtrip() {
+        let mut dictionary = Dictionary::default();
+        dictionary.add_names("curated", 1, ["content/audio/music", "content/audio/menu"]);
+        dictionary.add_names("harvest", 0, ["bone_root"]);
+        let path = std::env::temp_dir().join("hd2re_compiled_roundtrip.bin");
+        compile(&dictionary, &path);
+        assert!(is_compiled(&path));
+
+        let compiled = CompiledDictionary::open(&path).unwrap();
+        assert_eq!(compiled.len(), 3);
+        let hash = stingray_hash(b"content/audio/menu");
+        assert_eq!(compiled.get(hash), Some("content/audio/menu"));
+        assert_eq!(compiled.source_of(hash).unwrap().name, "curated");
+        assert_eq!(
+            compiled.get_thin(stingray_thin_hash(b"bone_root")),
+            Some("bone_root")
+        );
+        assert_eq!(compiled.get(0), None);
+
+        let back = compiled.to_dictionary();
+        assert_eq!(back.len(), 3);
+        assert_eq!(back.source_of(hash).unwrap().priority, 1);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn thin_keeps_priority_winner() {
+        // Two names sharing a thin hash
+        let mut seen = std::collections::HashMap::new();
+        let (a, b) = (0..)
+            .map(|i| format!("n{i}"))
+            .find_map(|name| {
+                let thin = stingray_thin_hash(name.as_bytes());
+                seen.insert(thin, name.clone()).map(|other| (other, name))
+            })
+            .unwrap();
+        let path = std::env::temp_dir().join("hd2re_compiled_thin.bin");
+        for (low, high) in [(&a, &b), (&b, &a)] {
+            let mut dictionary = Dictionary::default();
+            dictionary.add_names("high", 1, [high.as_str()]);
+            dictionary.add_names("low", 0, [low.as_str()]);
+            compile(&dictionary, &path);
+            let compiled = CompiledDictionary::open(&path).unwrap();
+            let thin = stingray_thin_hash(a.as_bytes());
+            assert_eq!(compiled.get_thin(thin), Some(high.as_str()));
+        }
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,255 @@
+//! Name guessing: hash generated candidates and keep the ones that are asset ids or type hashes.
+//!
+//! Candidates come from templates (`content/audio/{word}`) filled with the words of the known
+//! names, and from every known directory recombined with every known file name, optionally in a
+//! language folder. Each base candidate is also tried with numeric suffixes. The work is split in
+//! tasks, one per template or directory, run on all the cores. Completed tasks are saved with the
+//! hits so far, an interrupted run picks up where it stopped.
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use speedy::{Readable, Writable};
+use strum::IntoEnumIterator;
+
+use crate::hash::{stingray_hash, Dictionary, NoHash};
+use crate::index::HD2Index;
+use crate::parse::DataType;
+
+/// Folders localized assets live in
+const LANGUAGES: &[&str] = &[
+    "en",
+    "en_us",
+    "english(us)",
+    "de",
+    "german",
+    "es",
+    "spanish(spain)",
+    "fr",
+    "french(france)",
+    "it",
+    "italian",
+    "ja",
+    "japanese",
+    "ko",
+    "korean",
+    "pl",
+    "polish",
+    "pt",
+    "portuguese(brazil)",
+    "ru",
+    "russian",
+    "zh",
+    "chinese",
+];
+
+#[derive(Debug, Clone)]
+pub enum Task {
+    /// A template with a `{word}` placeholder
+    Template(String),
+    /// A directory, ending with '/', to combine with every file name
+    Directory(String),
+}
+
+impl Task {
+    /// Stable key of the task in the progress file
+    fn key(&self) -> String {
+        match self {
+            Task::Template(t) => format!("template:{t}"),
+            Task::Directory(d) => format!("dir:{d}"),
+        }
+    }
+}
+
+#[derive(Default, Readable, Writable)]
+pub struct Progress {
+    /// Keys of the completed tasks
+    pub done: BTreeSet<String>,
+    pub hits: Vec<(u64, String)>,
+}
+
+pub struct Cracker {
+    pub tasks: Vec<Task>,
+    /// Words to fill the templates with
+    pub words: Vec<String>,
+    /// File names to put in the directories
+    pub leaves: Vec<String>,
+    /// Suffixes `_0` to `_<max>` (and zero padded) are tried on every candidate
+    pub suffix_max: u32,
+    /// Hashes we are looking for: unnamed asset ids and type hashes
+    targets: HashSet<u64, NoHash>,
+}
+
+impl Cracker {
+    /// Use the known names as words, file names and directories, and `templates` as extra tasks
+    pub fn new(index: &HD2Index, dictionary: &Dictionary, templates: Vec<String>) -> Self {
+        let mut words = BTreeSet::new();
+        let mut leaves = BTreeSet::new();
+        let mut dirs = BTreeSet::new();
+        for name in dictionary.names() {
+            let (dir, leaf) = match name.rfind('/') {
+                Some(i) => (&name[..=i], &name[i + 1..]),
+                None => ("", name),
+            };
+            if !dir.is_empty() {
+                dirs.insert(dir.to_owned());
+            }
+            leaves.insert(leaf.to_owned());
+            words.extend(leaf.split(['_', '.']).map(str::to_owned));
+            words.insert(leaf.to_owned());
+        }
+        words.remove("");
+
+        let mut targets = HashSet::with_hasher(NoHash);
+        targets.extend(index.ids().filter(|&id| dictionary.get(id).is_none()));
+        targets.extend(DataType::iter().map(|t| t as u64));
+
+        let tasks = templates
+            .into_iter()
+            .map(Task::Template)
+            .chain(dirs.into_iter().map(Task::Directory))
+            .collect();
+        Self {
+            tasks,
+            words: words.into_iter().collect(),
+            leaves: leaves.into_iter().collect(),
+            suffix_max: 32,
+            targets,
+        }
+    }
+
+    /// Call `f` with `base` and its suffixed variants
+    fn with_suffixes(&self, base: &str, f: &mut impl FnMut(&str)) {
+        f(base);
+        let mut candidate = String::with_capacity(base.len() + 4);
+        for n in 0..=self.suffix_max {
+            for padded in [false, true] {
+                if padded && n >= 10 {
+                    continue;
+                }
+                candidate.clear();
+                candidate.push_str(base);
+                if padded {
+                    write!(candidate, "_{n:02}")
+                } else {
+                    write!(candidate, "_{n}")
+                }
+                .unwrap();
+                f(&candidate);
+            }
+        }
+    }
+
+    /// Returns the hits of one task
+    fn run_task(&self, task: &Task) -> Vec<(u64, String)> {
+        let mut hits = Vec::new();
+        let mut check = |candidate: &str| {
+            let hash = stingray_hash(candidate.as_bytes());
+            if self.targets.contains(&hash) {
+                hits.push((hash, candidate.to_owned()));
+            }
+        };
+        match task {
+            Task::Template(template) => {
+                for word in &self.words {
+                    self.with_suffixes(&template.replace("{word}", word), &mut check);
+                }
+            }
+            Task::Directory(dir) => {
+                for leaf in &self.leaves {
+                    self.with_suffixes(&format!("{dir}{leaf}"), &mut check);
+                    for lang in LANGUAGES {
+                        self.with_suffixes(&format!("{dir}{lang}/{leaf}"), &mut check);
+                    }
+                }
+            }
+        }
+        hits
+    }
+
+    /// Run the tasks not done yet on all the cores. Hits are appended to `hits_path`, progress is
+    /// saved next to it and reloaded from there.
+    pub fn run(&self, hits_path: impl AsRef<Path>) -> Progress {
+        let hits_path = hits_path.as_ref();
+        let progress_path = progress_path(hits_path);
+        let progress = Progress::read_from_file(&progress_path).unwrap_or_default();
+        let pending: Vec<_> = self
+            .tasks
+            .iter()
+            .filter(|t| !progress.done.contains(&t.key()))
+            .collect();
+        println!(
+            "{} tasks, {} done, {} targets, {} words, {} file names",
+            self.tasks.len(),
+            progress.done.len(),
+            self.targets.len(),
+            self.words.len(),
+            self.leaves.len()
+        );
+
+        let start = Instant::now();
+        let hits_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(hits_path)
+            .unwrap();
+        let state = Mutex::new((progress, hits_file));
+        let next = AtomicUsize::new(0);
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        thread::scope(|s| {
+            for _ in 0..threads {
+                s.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(task) = pending.get(i) else {
+                        break;
+                    };
+                    let hits = self.run_task(task);
+                    let mut state = state.lock().unwrap();
+                    let (progress, hits_file) = &mut *state;
+                    for (hash, name) in hits {
+                        println!("hit: {hash:016x} -> {name}");
+                        writeln!(hits_file, "{name}").unwrap();
+                        progress.hits.push((hash, name));
+                    }
+                    progress.done.insert(task.key());
+                    // Hits are flushed before the task is recorded as done
+                    hits_file.flush().unwrap();
+                    progress.write_to_file(&progress_path).unwrap();
+                    if progress.done.len() % 100 == 0 {
+                        println!("Task {}/{}", progress.done.len(), self.tasks.len());
+                    }
+                });
+            }
+        });
+        let (progress, _) = state.into_inner().unwrap();
+        println!(
+            "{} hits in {} ms",
+            progress.hits.len(),
+            start.elapsed().as_millis()
+        );
+        progress
+    }
+}
+
+fn progress_path(hits_path: &Path) -> PathBuf {
+    let mut path = hits_path.as_os_str().to_owned();
+    path.push(".progress.bin");
+    PathBuf::from(path)
+}
+
+/// Read templates, one per line, skipping empty lines and `//` comments like the dictionary
+pub fn load_templates(path: impl AsRef<Path>) -> Vec<String> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .filter(|l| !l.is_empty() && !l.starts_with("//"))
+        .map(str::to_owned)
+        .collect()
+}
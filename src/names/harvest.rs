@@ -0,0 +1,184 @@
+//! Harvest names from the strings embedded in the assets.
+//!
+//! Paths, bone names and event names often appear in plain text in some payload. Every string and
+//! every run of its path components is hashed, with and without its extension, and kept if it
+//! names an asset the dictionary doesn't know yet.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::Instant;
+
+use strum::IntoEnumIterator;
+
+use crate::decode::scan::find_strings;
+use crate::hash::{stingray_hash_batch, Dictionary, NoHash, SOURCE_COMMENT};
+use crate::index::{HD2Index, Part};
+use crate::parse::DataType;
+
+/// Shortest string worth hashing
+const MIN_LEN: usize = 4;
+/// Strings with more path components are only split on their last ones
+const MAX_COMPONENTS: usize = 8;
+
+#[derive(Debug)]
+pub struct Hit {
+    pub id: u64,
+    pub name: String,
+    /// Asset and part the string was found in
+    pub source: u64,
+    pub part: Part,
+}
+
+/// Candidate names from a string: itself and every run of its path components, with and without
+/// the extension
+pub fn candidates(s: &str) -> Vec<String> {
+    let s = s.trim().replace('\\', "/");
+    let components: Vec<_> = s.split('/').filter(|c| !c.is_empty()).collect();
+    let components = &components[components.len().saturating_sub(MAX_COMPONENTS)..];
+    let mut candidates = vec![s.clone()];
+    for start in 0..components.len() {
+        for end in start + 1..=components.len() {
+            let path = components[start..end].join("/");
+            if let Some((stem, _)) = path.rsplit_once('.') {
+                if !stem.is_empty() && !stem.ends_with('/') {
+                    candidates.push(stem.to_owned());
+                }
+            }
+            candidates.push(path);
+        }
+    }
+    candidates.sort();
+    candidates.dedup();
+    candidates
+}
+
+/// Scan the parts of every asset for names of assets missing from `dictionary`
+pub fn harvest(index: &HD2Index, dictionary: &Dictionary) -> Vec<Hit> {
+    let mut ids: Vec<_> = index.ids().collect();
+    ids.sort_by_key(|&id| (index[id].file_id, index[id].record.offset));
+    let mut found: HashSet<u64, NoHash> = HashSet::with_hasher(NoHash);
+    let mut hits = Vec::new();
+    let start = Instant::now();
+    for (i, &source) in ids.iter().enumerate() {
+        for part in Part::iter() {
+            let Ok(bytes) = index.load_part_bytes(source, part) else {
+                continue;
+            };
+            let names: Vec<_> = find_strings(&bytes, MIN_LEN)
+                .iter()
+                .flat_map(|s| candidates(&s.value))
+                .collect();
+            let keys: Vec<_> = names.iter().map(String::as_bytes).collect();
+            let mut hashes = vec![0; keys.len()];
+            stingray_hash_batch(&keys, &mut hashes);
+            for (name, id) in names.into_iter().zip(hashes) {
+                if index.contains(id) && dictionary.get(id).is_none() && found.insert(id) {
+                    println!("hit: {id:016x} -> {name} (in {source:016x} {part})");
+                    hits.push(Hit {
+                        id,
+                        name,
+                        source,
+                        part,
+                    });
+                }
+            }
+        }
+        if i % 1000 == 0 {
+            println!("Scanned {i}/{}", ids.len());
+        }
+    }
+    println!(
+        "Harvested {} names in {} ms",
+        hits.len(),
+        start.elapsed().as_millis()
+    );
+    hits
+}
+
+/// Add the strings of every `hash_lookup` asset to `dictionary`, one source per asset. Their layout
+/// isn't known, so every string and its candidates are added, names only matter by their hash.
+/// Returns the number of assets read.
+pub fn add_hash_lookups(index: &HD2Index, dictionary: &mut Dictionary, priority: i32) -> usize {
+    let mut ids: Vec<_> = index.ids_of_type(DataType::hash_lookup).collect();
+    ids.sort();
+    for &id in &ids {
+        let Ok(bytes) = index.load_data_bytes(id) else {
+            continue;
+        };
+        let names = find_strings(&bytes, MIN_LEN)
+            .iter()
+            .flat_map(|s| candidates(&s.value))
+            .collect::<Vec<_>>();
+        dictionary.add_names(&format!("hash_lookup {id:016x}"), priority, names);
+    }
+    ids.len()
+}
+
+/// Names of the hits by the asset and part they were found in
+fn by_source(hits: &[Hit]) -> BTreeMap<String, Vec<&str>> {
+    let mut by_source: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for hit in hits {
+        by_source
+            .entry(format!("harvest {:016x} {}", hit.source, hit.part))
+            .or_default()
+            .push(hit.name.as_str());
+    }
+    by_source
+}
+
+/// Append the hits to the dictionary file, under a comment naming the asset they come from
+pub fn append_to_dictionary(hits: &[Hit], path: impl AsRef<Path>) {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .unwrap();
+    for (source, names) in by_source(hits) {
+        writeln!(file, "{SOURCE_COMMENT}{source}").unwrap();
+        for name in names {
+            writeln!(file, "{name}").unwrap();
+        }
+    }
+}
+
+/// Add the hits to `dictionary` with the sources [`append_to_dictionary`] writes
+pub fn add_to_dictionary(hits: &[Hit], dictionary: &mut Dictionary) {
+    for (source, names) in by_source(hits) {
+        dictionary.add_names(&source, 0, names);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::names::harvest::candidates;
+
+    #[test]
+    fn candidates_of_a_path() {
+        assert_eq!(
+            candidates(" Content\\units//foo.unit "),
+            [
+                "Content",
+                "Content/units",
+                "Content/units//foo.unit",
+                "Content/units/foo",
+                "Content/units/foo.unit",
+                "foo",
+                "foo.unit",
+                "units",
+                "units/foo",
+                "units/foo.unit",
+            ]
+        );
+    }
+
+    #[test]
+    fn candidates_keep_the_last_components() {
+        let path = "a/b/c/d/e/f/g/h/i/j";
+        let candidates = candidates(path);
+        assert!(candidates.contains(&path.to_owned()));
+        assert!(candidates.contains(&"c/d/e/f/g/h/i/j".to_owned()));
+        assert!(!candidates.iter().any(|c| c.starts_with("b/") || c == "a"));
+    }
+}
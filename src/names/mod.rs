@@ -0,0 +1,5 @@
+//! Finding names for the hashes to grow the dictionary, and storing them.
+
+pub mod compiled;
+pub mod crack;
+pub mod harvest;
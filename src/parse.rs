@@ -1,5 +1,6 @@
 use binrw::io::SeekFrom;
 use binrw::BinRead;
+use serde::Serialize;
 use speedy::{Readable, Writable};
 use strum::{Display, EnumIter};
 
@@ -27,7 +28,20 @@ pub struct TypeHeader {
 
 /// Hash comes from hashing the enum name
 #[allow(non_camel_case_types)]
-#[derive(BinRead, Debug, Readable, Writable, Eq, PartialEq, EnumIter, Copy, Clone, Display)]
+#[derive(
+    BinRead,
+    Debug,
+    Readable,
+    Writable,
+    Serialize,
+    Eq,
+    PartialEq,
+    Hash,
+    EnumIter,
+    Copy,
+    Clone,
+    Display,
+)]
 #[br(repr = u64)]
 #[speedy(tag_type = u64)]
 #[repr(u64)]
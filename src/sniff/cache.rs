@@ -0,0 +1,279 @@
+//! Sniff results keyed by the content of the parts, shared by the sniffers.
+//!
+//! Results are stored by a hash of the part bytes, so identical parts are sniffed once and a
+//! result survives its asset moving to another archive. Every part also remembers where it was
+//! read from, archive modification time included: a part whose record and archive are unchanged
+//! isn't even read on the next run, only new or changed assets and patched archives are. The cache
+//! is saved every few thousand sniffs, an interrupted run resumes from the last save.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::{Instant, UNIX_EPOCH};
+
+use speedy::{Readable, Writable};
+use strum::IntoEnumIterator;
+
+use crate::hash::{stingray_hash, NoHash};
+use crate::index::{AssetMap, HD2Index, Part};
+
+/// New results between two saves
+const CHECKPOINT: usize = 2000;
+/// Start of a cache file
+const MAGIC: &[u8; 8] = b"HD2SNIFF";
+/// Bumped when the layout or the location keys change, older caches are dropped
+const VERSION: u32 = 1;
+
+/// Where a part was read from and the hash of its content
+#[derive(Debug, Readable, Writable, Copy, Clone, PartialEq)]
+struct PartKey {
+    location: u64,
+    content: u64,
+}
+
+#[derive(Debug, Readable, Writable)]
+pub struct SniffCache<R> {
+    /// Result by content hash
+    results: HashMap<u64, R, NoHash>,
+    /// Keys of the data, stream and gpu parts of every asset, `None` if empty
+    parts: AssetMap<[Option<PartKey>; 3]>,
+}
+
+impl<R> Default for SniffCache<R> {
+    fn default() -> Self {
+        Self {
+            results: HashMap::with_hasher(NoHash),
+            parts: AssetMap::with_hasher(NoHash),
+        }
+    }
+}
+
+/// Modification time of the archive file holding `part`, in nanoseconds, 0 if unknown
+fn modified(index: &HD2Index, file_id: u64, part: Part) -> u64 {
+    let path = match part {
+        Part::Data => index.resolve_data_file(file_id),
+        Part::Stream => index.resolve_stream_file(file_id),
+        Part::Gpu => index.resolve_gpu_file(file_id),
+    };
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// Hash of the archive, its modification time, offset and size of a part, changes when the asset
+/// is repacked or its archive patched in place. `times` caches the modification times.
+fn location(
+    index: &HD2Index,
+    id: u64,
+    part: Part,
+    times: &mut HashMap<(u64, u8), u64>,
+) -> Option<u64> {
+    let entry = &index[id];
+    let r = &entry.record;
+    let (offset, size) = match part {
+        Part::Data => (r.offset, r.data_size),
+        Part::Stream => (r.stream_offset as u64, r.stream_size),
+        Part::Gpu => (r.gpu_offset, r.gpu_size),
+    };
+    if size == 0 {
+        return None;
+    }
+    let modified = *times
+        .entry((entry.file_id, part as u8))
+        .or_insert_with(|| modified(index, entry.file_id, part));
+    let mut key = [0; 29];
+    key[..8].copy_from_slice(&entry.file_id.to_le_bytes());
+    key[8..16].copy_from_slice(&offset.to_le_bytes());
+    key[16..20].copy_from_slice(&size.to_le_bytes());
+    key[20] = part as u8;
+    key[21..].copy_from_slice(&modified.to_le_bytes());
+    Some(stingray_hash(&key))
+}
+
+impl<R> SniffCache<R>
+where
+    R: for<'a> Readable<'a, speedy::LittleEndian> + Writable<speedy::LittleEndian>,
+{
+    /// Load the cache at `path`, or start an empty one if it is missing or of another version
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let Ok(bytes) = fs::read(path) else {
+            return Self::default();
+        };
+        bytes
+            .strip_prefix(MAGIC.as_slice())
+            .and_then(|b| b.strip_prefix(VERSION.to_le_bytes().as_slice()))
+            .and_then(|payload| Self::read_from_buffer(payload).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, path: &Path) {
+        let mut out = MAGIC.to_vec();
+        out.extend(VERSION.to_le_bytes());
+        out.extend(self.write_to_vec().unwrap());
+        fs::write(path, out).unwrap();
+    }
+
+    /// Result for a part of an asset, if it was sniffed
+    pub fn get(&self, id: u64, part: Part) -> Option<&R> {
+        let key = self.parts.get(&id)?[part as usize]?;
+        self.results.get(&key.content)
+    }
+
+    /// Results of the data, stream and gpu parts of every asset sniffed
+    pub fn iter(&self) -> impl Iterator<Item = (u64, [Option<&R>; 3])> + '_ {
+        self.parts.iter().map(|(&id, keys)| {
+            (
+                id,
+                keys.map(|k| k.and_then(|k| self.results.get(&k.content))),
+            )
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// Sniff the parts that are new or changed since the last run with `sniff`, saving to `path`
+    /// regularly.
+    pub fn update(
+        &mut self,
+        index: &HD2Index,
+        path: impl AsRef<Path>,
+        mut sniff: impl FnMut(Part, &[u8]) -> R,
+    ) {
+        let path = path.as_ref();
+        let start = Instant::now();
+        let mut ids: Vec<_> = index.ids().collect();
+        ids.sort_by_key(|&id| (index[id].file_id, index[id].record.offset));
+        // Forget the assets that left the index
+        self.parts.retain(|id, _| index.contains(*id));
+
+        let mut times = HashMap::new();
+        let (mut sniffed, mut since_save) = (0, 0);
+        for (i, &id) in ids.iter().enumerate() {
+            let mut keys = self.parts.get(&id).copied().unwrap_or([None; 3]);
+            for part in Part::iter() {
+                let location = location(index, id, part, &mut times);
+                let known = keys[part as usize]
+                    .filter(|k| Some(k.location) == location)
+                    .is_some_and(|k| self.results.contains_key(&k.content));
+                if known {
+                    continue;
+                }
+                keys[part as usize] = location.and_then(|location| {
+                    let bytes = index.load_part_bytes(id, part).ok()?;
+                    // Results can depend on the part, identical parts of the same kind share them
+                    let content = stingray_hash(&bytes) ^ part as u64;
+                    if let Entry::Vacant(e) = self.results.entry(content) {
+                        e.insert(sniff(part, &bytes));
+                        sniffed += 1;
+                        since_save += 1;
+                    }
+                    Some(PartKey { location, content })
+                });
+            }
+            self.parts.insert(id, keys);
+            if since_save >= CHECKPOINT {
+                self.save(path);
+                since_save = 0;
+            }
+            if i % 1000 == 0 {
+                println!("Processed {i}/{}", ids.len());
+            }
+        }
+        // Drop the results no part has anymore, of changed parts and of assets that left
+        let live: HashSet<u64, NoHash> = self
+            .parts
+            .values()
+            .flat_map(|keys| keys.iter().flatten().map(|k| k.content))
+            .collect();
+        self.results.retain(|content, _| live.contains(content));
+        self.save(path);
+        println!(
+            "Sniffed {sniffed} new parts in {} ms, {} results cached",
+            start.elapsed().as_millis(),
+            self.results.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::time::{Duration, SystemTime};
+
+    use crate::index::{HD2Index, Part};
+    use crate::parse::DataType;
+    use crate::sniff::cache::SniffCache;
+
+    const FILE_ID: u64 = 0xaa;
+
+    /// An index of assets whose 4 bytes data parts follow each other in the archive `FILE_ID`
+    fn index(dir: &str, ids: &[u64]) -> HD2Index {
+        let parts: Vec<_> = (0..)
+            .zip(ids)
+            .map(|(i, &id)| (FILE_ID, id, DataType::lua, i * 4, 4))
+            .collect();
+        HD2Index::from_data_parts(dir, &parts)
+    }
+
+    /// Write the archive, with a modification time that changes on every call
+    fn write_archive(index: &HD2Index, content: &[u8], version: u64) {
+        let path = index.resolve_data_file(FILE_ID);
+        fs::write(&path, content).unwrap();
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 + version);
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[test]
+    fn update_sniffs_changes_only() {
+        let dir = std::env::temp_dir().join("hd2re_sniff_cache");
+        fs::create_dir_all(&dir).unwrap();
+        let dir = dir.to_str().unwrap();
+        let path = format!("{dir}/cache.bin");
+        let both = index(dir, &[1, 2]);
+        let mut sniffed = Vec::new();
+        let mut update = |cache: &mut SniffCache<String>, index: &HD2Index| {
+            cache.update(index, &path, |_, bytes: &[u8]| {
+                let label = String::from_utf8_lossy(bytes).into_owned();
+                sniffed.push(label.clone());
+                label
+            });
+        };
+
+        write_archive(&both, b"AAAABBBB", 0);
+        let mut cache = SniffCache::default();
+        update(&mut cache, &both);
+        update(&mut cache, &both);
+        assert_eq!(cache.get(2, Part::Data).map(String::as_str), Some("BBBB"));
+        // Patching the archive moves every part, only the changed one is sniffed again
+        write_archive(&both, b"AAAACCCC", 1);
+        update(&mut cache, &both);
+        assert_eq!(cache.get(2, Part::Data).map(String::as_str), Some("CCCC"));
+        assert_eq!(cache.results.len(), 2);
+        // Assets and results leave with the index
+        update(&mut cache, &index(dir, &[1]));
+        assert_eq!((cache.len(), cache.results.len()), (1, 1));
+        assert_eq!(sniffed, ["AAAA", "BBBB", "CCCC"]);
+
+        let saved = SniffCache::<String>::load(&path);
+        assert_eq!(saved.get(1, Part::Data).map(String::as_str), Some("AAAA"));
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[8] += 1;
+        fs::write(&path, bytes).unwrap();
+        assert!(SniffCache::<String>::load(&path).is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,44 +1,157 @@
-use crate::hash::NoHash;
-use crate::index::{AssetMap, HD2Index};
-use magic::cookie::Flags;
-use speedy::{Readable, Writable};
-use std::convert::TryInto;
+use std::error::Error;
+use std::path::PathBuf;
+use std::{env, fs};
 
-#[derive(Debug, Readable, Writable)]
-pub struct LibMagicSniff {
-    pub results: AssetMap<(String, String, String)>,
+use crate::hash::stingray_hash;
+use crate::index::Part;
+use crate::sniff::{Guess, Sniffer};
+use magic::cookie::{Cookie, DatabasePaths, Flags, Load};
+
+/// libmagic doesn't score its answers, its meaningful ones get this confidence
+const CONFIDENCE: f32 = 0.5;
+
+/// Signatures of the HD2 assets (Wwise, Stingray headers), embedded in the binary
+pub const HD2_MAGIC: &str = include_str!("../../magic/hd2.magic");
+
+/// What libmagic answers with
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Output {
+    /// Textual description, "Wwise soundbank, version 141"
+    #[default]
+    Description,
+    /// "audio/x-wwise"
+    Mime,
+    /// Extensions separated by '/', "wem"
+    Extension,
+}
+
+#[derive(Debug, Clone)]
+pub struct LibMagicConfig {
+    /// Databases to load, compiled or not. The system database when empty.
+    pub databases: Vec<PathBuf>,
+    /// Load [`HD2_MAGIC`] and ask it first, if libmagic accepts it
+    pub hd2_magic: bool,
+    pub output: Output,
+}
+
+impl Default for LibMagicConfig {
+    fn default() -> Self {
+        Self {
+            databases: Vec::new(),
+            hd2_magic: true,
+            output: Output::Description,
+        }
+    }
+}
+
+pub struct LibMagicSniffer {
+    /// Asked in order, the first meaningful answer is kept
+    cookies: Vec<Cookie<Load>>,
+    output: Output,
+}
+
+/// `databases`, or the system database when empty
+fn load(databases: &[PathBuf], flags: Flags) -> Result<Cookie<Load>, Box<dyn Error>> {
+    let databases = if databases.is_empty() {
+        DatabasePaths::default()
+    } else {
+        databases.to_vec().try_into()?
+    };
+    Ok(magic::Cookie::open(flags)?.load(&databases)?)
+}
+
+/// libmagic only loads text databases from files, write [`HD2_MAGIC`] to a temporary one named
+/// after its content
+fn load_hd2_magic(flags: Flags) -> Result<Cookie<Load>, Box<dyn Error>> {
+    let path = env::temp_dir().join(format!(
+        "hd2re-{:016x}.magic",
+        stingray_hash(HD2_MAGIC.as_bytes())
+    ));
+    if fs::read_to_string(&path).ok().as_deref() != Some(HD2_MAGIC) {
+        fs::write(&path, HD2_MAGIC)?;
+    }
+    Ok(magic::Cookie::open(flags)?.load(&[path].try_into()?)?)
 }
 
-impl LibMagicSniff {
-    pub fn run(index: &HD2Index) -> Self {
+impl LibMagicSniffer {
+    pub fn new(config: &LibMagicConfig) -> Self {
         println!("Using libmagic {}", magic::libmagic_version());
-        let cookie = magic::Cookie::open(Flags::empty())
-            .unwrap()
-            .load(&[r#"C:\apps\vcpkg\packages\libmagic_x64-windows-static-md\share\libmagic\misc\magic.mgc"#].try_into().unwrap())
-            .unwrap();
-        let mut results = AssetMap::with_capacity_and_hasher(index.len(), NoHash);
-        for (i, key) in index.ids().enumerate() {
-            let data = index
-                .load_data_bytes(key)
-                .map(|b| cookie.buffer(b.as_slice()).unwrap())
-                .unwrap_or(String::new());
-            let stream = index
-                .load_stream_bytes(key)
-                .map(|b| cookie.buffer(b.as_slice()).unwrap())
-                .unwrap_or(String::new());
-            let gpu = index
-                .load_gpu_bytes(key)
-                .map(|b| cookie.buffer(b.as_slice()).unwrap())
-                .unwrap_or(String::new());
-            results.insert(key, (data, stream, gpu));
-            if i % 1000 == 0 {
-                println!("Processed {i}/{}", index.len());
+        let flags = match config.output {
+            Output::Description => Flags::empty(),
+            Output::Mime => Flags::MIME_TYPE,
+            Output::Extension => Flags::EXTENSION,
+        };
+        let mut cookies = Vec::new();
+        if config.hd2_magic {
+            match load_hd2_magic(flags) {
+                Ok(cookie) => cookies.push(cookie),
+                Err(e) => eprintln!("HD2 signatures not loaded, going on without them: {e}"),
             }
         }
-        Self { results }
+        match load(&config.databases, flags) {
+            Ok(cookie) => cookies.push(cookie),
+            Err(e) => eprintln!("libmagic databases not loaded, going on without them: {e}"),
+        }
+        Self {
+            cookies,
+            output: config.output,
+        }
     }
 
     pub fn guess_is_worthless(guess: &str) -> bool {
-        matches!(guess, "data" | "empty" | "")
+        matches!(
+            guess,
+            "data" | "empty" | "" | "application/octet-stream" | "inode/x-empty" | "???"
+        )
+    }
+}
+
+impl Default for LibMagicSniffer {
+    fn default() -> Self {
+        Self::new(&LibMagicConfig::default())
+    }
+}
+
+impl Sniffer for LibMagicSniffer {
+    fn name(&self) -> &str {
+        match self.output {
+            Output::Description => "libmagic",
+            Output::Mime => "libmagic-mime",
+            Output::Extension => "libmagic-ext",
+        }
+    }
+
+    fn sniff(&mut self, part: Part, bytes: &[u8]) -> Vec<Guess> {
+        let mut label = None;
+        for cookie in &self.cookies {
+            match cookie.buffer(bytes) {
+                Ok(answer) if !Self::guess_is_worthless(&answer) => {
+                    label = Some(answer);
+                    break;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("libmagic failed on a {part} part, skipping it: {e}");
+                    return Vec::new();
+                }
+            }
+        }
+        label
+            .map(|label| Guess {
+                label,
+                confidence: CONFIDENCE,
+                part,
+            })
+            .into_iter()
+            .collect()
+    }
+
+    /// Descriptions go on with details after a comma, "Wwise soundbank, version 141", only the
+    /// first field is kept. MIME types and extensions are already normalized.
+    fn normalize(&self, label: &str) -> String {
+        match self.output {
+            Output::Description => label.split(',').next().unwrap_or(label).trim().to_owned(),
+            Output::Mime | Output::Extension => label.to_owned(),
+        }
     }
 }
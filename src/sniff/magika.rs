@@ -1,20 +1,39 @@
-use crate::hash::NoHash;
-use crate::index::{AssetMap, HD2Index};
+use crate::index::Part;
+use crate::sniff::{Guess, Sniffer};
 use magika::MagikaSession;
 use ort::{
     CUDAExecutionProvider, DirectMLExecutionProvider, ExecutionProvider, GraphOptimizationLevel,
     Session, TensorRTExecutionProvider,
 };
-use speedy::{Readable, Writable};
 
-#[derive(Debug, Readable, Writable)]
-pub struct MagikaSniff {
-    pub labels: Vec<String>,
-    pub results: AssetMap<[[(f32, u32); 3]; 3]>,
+/// Loads the model on first use
+#[derive(Default)]
+pub struct MagikaSniffer {
+    session: Option<MagikaSession>,
 }
 
-impl MagikaSniff {
-    pub fn run(index: &HD2Index) -> Self {
+impl Sniffer for MagikaSniffer {
+    fn name(&self) -> &str {
+        "magika"
+    }
+
+    /// The top 3 labels
+    fn sniff(&mut self, part: Part, bytes: &[u8]) -> Vec<Guess> {
+        let magika = self.session.get_or_insert_with(Self::session);
+        let top = magika.identify_topk::<3>(bytes).unwrap();
+        top.into_iter()
+            .filter(|&(score, _)| score > 0.0)
+            .map(|(confidence, label)| Guess {
+                label: magika.labels()[label as usize].clone(),
+                confidence,
+                part,
+            })
+            .collect()
+    }
+}
+
+impl MagikaSniffer {
+    fn session() -> MagikaSession {
         ort::init_from(
             r#"C:\Users\Guillaume\Desktop\onnxruntime\build\Windows\Release\onnxruntime.dll"#,
         )
@@ -49,34 +68,10 @@ impl MagikaSniff {
         }
         println!();
 
-        let mut magika = MagikaSession::from(
+        MagikaSession::from(
             session_builder,
             "../magika/python/magika/models/standard_v1",
         )
-        .unwrap();
-        println!("Running inference");
-        let mut results = AssetMap::with_capacity_and_hasher(index.len(), NoHash);
-        for (i, key) in index.ids().enumerate() {
-            let data = index
-                .load_data_bytes(key)
-                .map(|b| magika.identify_topk::<3>(b.as_slice()).unwrap())
-                .unwrap_or([(0.0, 0); 3]);
-            let stream = index
-                .load_stream_bytes(key)
-                .map(|b| magika.identify_topk::<3>(b.as_slice()).unwrap())
-                .unwrap_or([(0.0, 0); 3]);
-            let gpu = index
-                .load_gpu_bytes(key)
-                .map(|b| magika.identify_topk::<3>(b.as_slice()).unwrap())
-                .unwrap_or([(0.0, 0); 3]);
-            results.insert(key, [data, stream, gpu]);
-            if i % 1000 == 0 {
-                println!("Processed {i}/{}", index.len());
-            }
-        }
-        Self {
-            labels: magika.labels().to_vec(),
-            results,
-        }
+        .unwrap()
     }
 }
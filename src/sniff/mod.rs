@@ -1,20 +1,149 @@
-use ort::ExecutionProvider;
+//! Content sniffing: guessing what the parts of an asset are from their bytes.
+//!
+//! Every detector implements [`Sniffer`] and returns [`Guess`]es. A [`Registry`] chains them in
+//! order and caches what each of them found.
+
+use std::path::Path;
+
+use serde::Serialize;
 use speedy::{Readable, Writable};
+use strum::IntoEnumIterator;
 
-use crate::index::HD2Index;
+use crate::index::{HD2Index, Part};
+use crate::sniff::cache::SniffCache;
 
+pub mod cache;
 pub mod libmagic;
 #[cfg(feature = "sniff-magika")]
 pub mod magika;
+pub mod signature;
+
+#[derive(Debug, Clone, Readable, Writable, Serialize)]
+pub struct Guess {
+    pub label: String,
+    /// From 0 to 1
+    pub confidence: f32,
+    pub part: Part,
+}
+
+pub trait Sniffer {
+    /// Short name, also used for the cache file
+    fn name(&self) -> &str;
+
+    /// What `bytes` could be, empty if the sniffer has no idea
+    fn sniff(&mut self, part: Part, bytes: &[u8]) -> Vec<Guess>;
+
+    /// `label` without what is specific to an asset, so that labels can be counted across assets
+    fn normalize(&self, label: &str) -> String {
+        label.to_owned()
+    }
+}
+
+/// Sniffers run in registration order. Once one of them is sure enough of a part, the next ones
+/// are skipped for it.
+pub struct Registry {
+    sniffers: Vec<Box<dyn Sniffer>>,
+    /// Confidence at which the chain stops, above 1 to always run every sniffer
+    pub stop_confidence: f32,
+    /// Results of each sniffer, once [`Registry::run`]
+    caches: Vec<SniffCache<Vec<Guess>>>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            sniffers: Vec::new(),
+            stop_confidence: 0.9,
+            caches: Vec::new(),
+        }
+    }
+}
+
+/// Concatenate the guesses of the chain until one is confident enough
+fn chain<'a>(guesses: impl Iterator<Item = &'a [Guess]>, stop_confidence: f32) -> Vec<Guess> {
+    let mut out = Vec::new();
+    for g in guesses {
+        out.extend_from_slice(g);
+        if g.iter().any(|g| g.confidence >= stop_confidence) {
+            break;
+        }
+    }
+    out
+}
+
+impl Registry {
+    pub fn register(&mut self, sniffer: impl Sniffer + 'static) -> &mut Self {
+        self.sniffers.push(Box::new(sniffer));
+        self
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.sniffers.iter().map(|s| s.name())
+    }
+
+    /// Run the chain on some bytes, without caching
+    pub fn sniff(&mut self, part: Part, bytes: &[u8]) -> Vec<Guess> {
+        let mut out = Vec::new();
+        for sniffer in &mut self.sniffers {
+            let guesses = sniffer.sniff(part, bytes);
+            let done = guesses.iter().any(|g| g.confidence >= self.stop_confidence);
+            out.extend(guesses);
+            if done {
+                break;
+            }
+        }
+        out
+    }
 
-pub(crate) fn sniff_wav(index: &HD2Index) {
-    for item in index.ids() {
-        if index
-            .load_n_stream_bytes::<4>(item)
-            .map(|b| &b == b"RIFF")
-            .unwrap_or(false)
-        {
-            println!("{item} : WAV ({:?})", index[item].record.type_id);
+    /// Run every sniffer on the assets new to its cache, `<dir>/hd2sniff.<name>.bin`. Each
+    /// sniffer sees every part so that the caches don't depend on the chain order.
+    pub fn run(&mut self, index: &HD2Index, dir: impl AsRef<Path>) {
+        self.caches.clear();
+        for sniffer in &mut self.sniffers {
+            let path = dir
+                .as_ref()
+                .join(format!("hd2sniff.{}.bin", sniffer.name()));
+            println!("Running {} sniffer", sniffer.name());
+            let mut cache = SniffCache::load(&path);
+            cache.update(index, &path, |part, bytes| sniffer.sniff(part, bytes));
+            self.caches.push(cache);
         }
     }
+
+    /// Guesses of the chain for a part of an asset, from the caches
+    pub fn guesses(&self, id: u64, part: Part) -> Vec<Guess> {
+        chain(
+            self.caches
+                .iter()
+                .map(|c| c.get(id, part).map_or(&[][..], Vec::as_slice)),
+            self.stop_confidence,
+        )
+    }
+
+    /// The most confident guess of every sniffer for a part of an asset, from the caches, with its
+    /// label normalized by the sniffer. Labels of different sniffers don't share a vocabulary.
+    pub fn best_guesses(&self, id: u64, part: Part) -> Vec<(&str, Option<Guess>)> {
+        self.sniffers
+            .iter()
+            .zip(&self.caches)
+            .map(|(sniffer, cache)| {
+                let best = cache
+                    .get(id, part)
+                    .and_then(|g| {
+                        g.iter()
+                            .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+                    })
+                    .map(|g| Guess {
+                        label: sniffer.normalize(&g.label),
+                        ..g.clone()
+                    });
+                (sniffer.name(), best)
+            })
+            .collect()
+    }
+
+    /// Guesses of the chain for every part of an asset
+    pub fn all_guesses(&self, id: u64) -> Vec<Guess> {
+        Part::iter().flat_map(|p| self.guesses(id, p)).collect()
+    }
 }
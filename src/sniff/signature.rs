@@ -0,0 +1,151 @@
+//! Magic number sniffer for the formats found in the archives, no native dependency needed.
+//!
+//! A signature at the start of a part is certain. Many assets prefix the actual payload with an
+//! engine header, so a signature found a bit further is still reported, with less confidence.
+//! Compressed streams have weak or no magic and get a low confidence.
+
+use crate::decode::havok::{PACKFILE_MAGIC, TAGFILE_MAGIC};
+use crate::decode::shader::{looks_like_container, DxContainer};
+use crate::decode::u16_at;
+use crate::index::Part;
+use crate::sniff::{Guess, Sniffer};
+
+/// How far into a part we look for a signature behind an engine header
+const SEARCH_WINDOW: usize = 0x100;
+/// Confidence of a signature found behind a header
+const EMBEDDED: f32 = 0.8;
+
+/// Formats identified by a magic alone
+const MAGICS: &[(&str, &[u8])] = &[
+    ("wwise_bank", b"BKHD"),
+    ("dds", b"DDS "),
+    ("luajit", b"\x1bLJ"),
+    ("lua", b"\x1bLua"),
+    ("havok_packfile", PACKFILE_MAGIC),
+    ("zstd", b"\x28\xB5\x2F\xFD"),
+    ("png", b"\x89PNG\r\n\x1a\n"),
+];
+
+/// Oodle compressor ids following the 0x8C/0xCC block header byte: Kraken, Mermaid and Selkie
+/// (which share an id), BitKnit, Leviathan
+const OODLE_COMPRESSORS: &[u8] = &[0x06, 0x0A, 0x0B, 0x0C];
+
+fn riff(bytes: &[u8]) -> Option<&'static str> {
+    if !bytes.starts_with(b"RIFF") || bytes.get(8..12) != Some(b"WAVE") {
+        return None;
+    }
+    // Wwise uses the extensible format tag for its own codecs
+    match u16_at(bytes, 20) {
+        Some(0xFFFF | 0xFFFE) => Some("wem"),
+        _ => Some("wav"),
+    }
+}
+
+fn bink(bytes: &[u8]) -> Option<&'static str> {
+    let version = *bytes.get(3)?;
+    if !version.is_ascii_alphabetic() {
+        return None;
+    }
+    match &bytes[..3] {
+        b"BIK" => Some("bink"),
+        b"KB2" => Some("bink2"),
+        _ => None,
+    }
+}
+
+fn shader(bytes: &[u8], offset: usize) -> Option<&'static str> {
+    if !looks_like_container(bytes, offset) {
+        return None;
+    }
+    match DxContainer::parse(&bytes[offset..]) {
+        Some(c) if c.is_dxil() => Some("dxil"),
+        _ => Some("dxbc"),
+    }
+}
+
+fn compressed(bytes: &[u8]) -> Option<(&'static str, f32)> {
+    let (&b0, &b1) = (bytes.first()?, bytes.get(1)?);
+    // CMF deflate with a 32K window, and the header checksum
+    if b0 == 0x78 && u16::from_be_bytes([b0, b1]) % 31 == 0 {
+        return Some(("zlib", 0.6));
+    }
+    if matches!(b0, 0x8C | 0xCC) && OODLE_COMPRESSORS.contains(&b1) {
+        return Some(("oodle", 0.4));
+    }
+    None
+}
+
+/// Every format `bytes` looks like, with a confidence
+pub fn sniff_signatures(bytes: &[u8]) -> Vec<(&'static str, f32)> {
+    let mut found = Vec::new();
+    let window = bytes.len().min(SEARCH_WINDOW);
+    let mut at = |offset: usize, label: &'static str| {
+        let confidence = if offset == 0 { 1.0 } else { EMBEDDED };
+        found.push((label, confidence));
+    };
+
+    for &(label, magic) in MAGICS {
+        if let Some(offset) = bytes[..(window + magic.len()).min(bytes.len())]
+            .windows(magic.len())
+            .position(|w| w == magic)
+        {
+            at(offset, label);
+        }
+    }
+    if let Some(offset) = (0..window)
+        .step_by(4)
+        .find(|&o| bytes.get(o + 4..o + 8) == Some(TAGFILE_MAGIC))
+    {
+        at(offset, "havok_tagfile");
+    }
+    if let Some((offset, label)) = (0..window).find_map(|o| shader(bytes, o).map(|l| (o, l))) {
+        at(offset, label);
+    }
+    if let Some(label) = riff(bytes).or_else(|| bink(bytes)) {
+        at(0, label);
+    }
+    if found.is_empty() {
+        found.extend(compressed(bytes));
+    }
+    found.sort_by(|a, b| b.1.total_cmp(&a.1));
+    found
+}
+
+pub struct SignatureSniffer;
+
+impl Sniffer for SignatureSniffer {
+    fn name(&self) -> &str {
+        "signature"
+    }
+
+    fn sniff(&mut self, part: Part, bytes: &[u8]) -> Vec<Guess> {
+        sniff_signatures(bytes)
+            .into_iter()
+            .map(|(label, confidence)| Guess {
+                label: label.to_owned(),
+                confidence,
+                part,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures() {
+        let mut wem = b"RIFF\0\0\0\0WAVEfmt \x18\0\0\0".to_vec();
+        wem.extend([0xFF, 0xFF, 2, 0]);
+        assert_eq!(sniff_signatures(&wem), [("wem", 1.0)]);
+
+        let mut texture = vec![0; 0xC0];
+        texture.extend(b"DDS |\0\0\0");
+        assert_eq!(sniff_signatures(&texture), [("dds", EMBEDDED)]);
+
+        assert_eq!(sniff_signatures(b"KB2j\0\0\0\0"), [("bink2", 1.0)]);
+        assert_eq!(sniff_signatures(b"\x78\x9c\x01\x02"), [("zlib", 0.6)]);
+        assert!(sniff_signatures(b"\0\0\0\0\0\0\0\0").is_empty());
+    }
+}